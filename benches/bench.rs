@@ -0,0 +1,97 @@
+//! Rough timings of `StringWrapper` operations, without a benchmarking framework.
+//!
+//! Run with `cargo bench`.
+extern crate string_wrapper;
+
+use std::fmt::Write;
+use std::hint::black_box;
+use std::str::FromStr;
+use std::time::Instant;
+
+use string_wrapper::StringWrapper;
+
+fn bench<F: FnMut()>(name: &str, iters: u32, mut f: F) {
+    let start = Instant::now();
+    for _ in 0..iters {
+        f();
+    }
+    println!("{:<40} {:>12.2?}/iter", name, start.elapsed() / iters);
+}
+
+fn retain() {
+    let text = "The quick brown fox — jumps over the lazy dog, 1234567890 times! ".repeat(60);
+    let s: StringWrapper<[u8; 4096]> = StringWrapper::from_str(&text).unwrap();
+
+    bench("retain_ascii", 10_000, || {
+        let mut s = black_box(s);
+        s.retain_ascii(|b| !b.is_ascii_digit());
+        black_box(s);
+    });
+    bench("retain via chars()", 10_000, || {
+        let s = black_box(s);
+        let mut out = StringWrapper::new([0_u8; 4096]);
+        for c in s.chars().filter(|c| !c.is_ascii_digit()) {
+            out.push(c).unwrap();
+        }
+        black_box(out);
+    });
+}
+
+fn push() {
+    let mut s = StringWrapper::new(vec![0_u8; 1_000_000]);
+    bench("push 1M ASCII chars", 20, || {
+        s.truncate(0);
+        for _ in 0..1_000_000 {
+            s.push(black_box('a')).unwrap();
+        }
+        black_box(&s);
+    });
+}
+
+fn write_char() {
+    let mut s = StringWrapper::new(vec![0_u8; 1_000_000]);
+    bench("write! 1M chars", 20, || {
+        s.truncate(0);
+        for _ in 0..1_000_000 {
+            write!(s, "{}", black_box('a')).unwrap();
+        }
+        black_box(&s);
+    });
+}
+
+fn push_str_all() {
+    let parts = ["key", "=", "value", "; ", "other_key", "=", "42", "; "];
+    let mut s = StringWrapper::new([0_u8; 64]);
+    bench("push_str 8 parts", 1_000_000, || {
+        s.truncate(0);
+        for part in black_box(&parts) {
+            s.push_str(part).unwrap();
+        }
+        black_box(&s);
+    });
+    bench("push_str_all 8 parts", 1_000_000, || {
+        s.truncate(0);
+        s.push_str_all(black_box(&parts)).unwrap();
+        black_box(&s);
+    });
+}
+
+fn push_str_sizes() {
+    for &size in &[64, 1024, 16 * 1024, 64 * 1024] {
+        let text = "a".repeat(size);
+        let mut s = StringWrapper::new(vec![0_u8; size]);
+        bench(&format!("push_str {} bytes", size), 100_000, || {
+            s.truncate(0);
+            s.push_str(black_box(&text)).unwrap();
+            black_box(&s);
+        });
+    }
+}
+
+fn main() {
+    retain();
+    push();
+    write_char();
+    push_str_all();
+    push_str_sizes();
+}
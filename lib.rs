@@ -1,21 +1,33 @@
 //! provides `StringWrapper`, most useful for stack-based strings.
 #![deny(missing_docs)]
 
+#[cfg(feature = "use_heapless")]
+extern crate heapless;
 #[cfg(feature = "use_serde")]
 extern crate serde;
+#[cfg(feature = "use_unicode_segmentation")]
+extern crate unicode_segmentation;
 
+use std::char;
 use std::cmp;
+use std::convert::TryFrom;
 use std::fmt;
 use std::hash;
-use std::io::Write;
+use std::io;
+use std::iter;
+use std::iter::Peekable;
+use std::mem;
 use std::ops;
-use std::ptr;
 use std::str;
 use std::str::FromStr;
 
 /// Like `String`, but with a fixed capacity and a generic backing bytes storage.
 ///
 /// Use e.g. `StringWrapper<[u8; 4]>` to have a string without heap memory allocation.
+///
+/// `Default` gives an empty string with the default buffer, which has no capacity at all for e.g.
+/// `[u8; 0]` or `Vec<u8>`. See `ensure_capacity_type!` to catch too-small array buffers at compile
+/// time.
 #[derive(Clone, Copy, Default)]
 pub struct StringWrapper<T>
 where
@@ -43,6 +55,33 @@ pub unsafe trait Buffer {
 /// fixed capacity, and we don't have a way to know what size vec we should return.
 // Besides, I'm not sure what the value of Buffer for Vec is anyway, when you could just use
 // String...
+///
+/// Your own fixed-size buffer types can implement it too, see `ArrayBuffer` for an example:
+///
+/// ```
+/// use std::str::FromStr;
+/// use string_wrapper::{Buffer, OwnedBuffer, StringWrapper};
+///
+/// struct Name([u8; 8]);
+///
+/// unsafe impl Buffer for Name {
+///     fn as_ref(&self) -> &[u8] {
+///         &self.0
+///     }
+///     fn as_mut(&mut self) -> &mut [u8] {
+///         &mut self.0
+///     }
+/// }
+///
+/// impl OwnedBuffer for Name {
+///     fn new() -> Self {
+///         Name([0; 8])
+///     }
+/// }
+///
+/// let name: StringWrapper<Name> = StringWrapper::from_str("radix").unwrap();
+/// assert_eq!(&*name, "radix");
+/// ```
 pub trait OwnedBuffer: Buffer {
     /// Creature a new buffer that can be used to initialize a `StringWrapper`.
     fn new() -> Self;
@@ -53,11 +92,43 @@ pub trait OwnedBuffer: Buffer {
 pub enum Error {
     /// Returned from from_str and push in case when not enough extra capacity is available
     InsufficientLength {
-        /// Amount of space required for this operation to complete
+        /// Amount of space required for this operation to complete, saturating at `usize::MAX`
         expected: usize,
         /// available space
         actual: usize,
     },
+    /// Returned when bytes to be added to the string are not well-formed UTF-8
+    InvalidUtf8(str::Utf8Error),
+    /// Returned when UTF-16 data to be added to the string contains an unpaired surrogate
+    InvalidUtf16(char::DecodeUtf16Error),
+    /// Returned when replacing a character in place with one of a different UTF-8 length
+    CharLengthMismatch {
+        /// UTF-8 length of the character to replace
+        from: usize,
+        /// UTF-8 length of its replacement
+        to: usize,
+    },
+    /// Returned when a string to be exported as a C string contains a NUL byte
+    InteriorNul {
+        /// byte offset of the first NUL
+        position: usize,
+    },
+    /// Returned when a byte offset is past the end of the string or inside a code point
+    NotCharBoundary {
+        /// the offending byte offset
+        index: usize,
+    },
+}
+
+/// Where `StringWrapper::pad_to` puts the string relative to the padding.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Alignment {
+    /// Pad after the string.
+    Left,
+    /// Pad before the string.
+    Right,
+    /// Pad on both sides, with the extra fill character if any after the string.
+    Center,
 }
 
 impl<T> StringWrapper<T>
@@ -69,6 +140,18 @@ where
         StringWrapper { len: 0, buffer }
     }
 
+    /// Create a string from its backing storage, initialized with a copy of `s`.
+    ///
+    /// Unlike `from_str`, this lets you supply the buffer, e.g. one taken from a pool.
+    ///
+    /// # Errors
+    /// Return `Error::InsufficientLength` if `s` doesn't fit in `buffer`.
+    pub fn copy_from_str(buffer: T, s: &str) -> Result<Self, Error> {
+        let mut sw = StringWrapper::new(buffer);
+        sw.push_str(s)?;
+        Ok(sw)
+    }
+
     /// Unsafely create a string from its components.
     ///
     /// # Safety
@@ -85,6 +168,12 @@ where
         self.buffer
     }
 
+    /// Consume the string and return the backing storage and the length, the inverse of
+    /// `from_raw_parts`.
+    pub fn into_raw_parts(self) -> (T, usize) {
+        (self.buffer, self.len)
+    }
+
     /// View the backing storage as a bytes slice.
     pub fn buffer(&self) -> &[u8] {
         self.buffer.as_ref()
@@ -139,6 +228,11 @@ where
         self.capacity() - self.len
     }
 
+    /// Return by how many bytes the string can grow, like `extra_capacity`.
+    pub fn remaining(&self) -> usize {
+        self.extra_capacity()
+    }
+
     /// Return the slice of unused bytes after the string
     pub fn extra_bytes_mut(&mut self) -> &mut [u8] {
         &mut self.buffer.as_mut()[self.len..]
@@ -151,10 +245,10 @@ where
     ///
     /// Return `Ok` with the code point appended, or `Err` with the string unchanged.
     pub fn push(&mut self, c: char) -> Result<(), Error> {
-        let new_len = self.len + c.len_utf8();
+        // Buffers are at most `isize::MAX` bytes long, so a saturated length never fits.
+        let new_len = self.len.saturating_add(c.len_utf8());
         if new_len <= self.capacity() {
-            // FIXME: use `c.encode_utf8` once it’s stable.
-            write!(self.extra_bytes_mut(), "{}", c).unwrap();
+            c.encode_utf8(self.extra_bytes_mut());
             self.len = new_len;
             Ok(())
         } else {
@@ -172,7 +266,7 @@ where
         if self.extra_capacity() < s.len() {
             return Err(Error::InsufficientLength{ expected: s.len(), actual: self.extra_capacity() })
         }
-        copy_memory(s.as_bytes(), self.extra_bytes_mut());
+        self.extra_bytes_mut()[..s.len()].copy_from_slice(s.as_bytes());
         self.len += s.len();
         Ok(())
     }
@@ -199,458 +293,4174 @@ where
         self.push_str(s).unwrap();
         result
     }
-}
-
-impl<T: OwnedBuffer> FromStr for StringWrapper<T> {
-    type Err = Error;
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let buffer = T::new();
-        let mut sw = StringWrapper::new(buffer);
-        let res = sw.push_str(s);
-        res.map(|_: ()| sw)
+    /// Append the `Display` output of each item, separated by `sep`.
+    ///
+    /// # Errors
+    /// Return `Error::InsufficientLength` with the string unchanged if the extra capacity is not
+    /// sufficient for the whole list.
+    pub fn push_display_list<D: fmt::Display>(
+        &mut self,
+        items: &[D],
+        sep: &str,
+    ) -> Result<(), Error> {
+        let mut appender = Appender::new(self);
+        for (i, item) in items.iter().enumerate() {
+            if i > 0 {
+                appender.push_str(sep);
+            }
+            // `Appender` never fails, it records overflow for `finish` instead.
+            let _ = fmt::Write::write_fmt(&mut appender, format_args!("{}", item));
+        }
+        appender.finish()
     }
-}
 
-impl<T: OwnedBuffer> StringWrapper<T> {
-    /// Safely construct a new `StringWrapper` from a &str. Unlike `from_str`, this method doesn't
-    /// panic when the &str is too big to fit into the buffer.
+    /// Return a case-folded copy of the string in a new `M`-byte array, suitable as a
+    /// case-insensitive key.
     ///
-    /// # Examples
+    /// Folding is approximated with `char::to_lowercase`, so e.g. `'ß'` is kept as is rather than
+    /// folded to `"ss"`. Since lowercasing can change the length in bytes, return `None` if the
+    /// result does not fit in `M` bytes.
+    pub fn case_fold_into<const M: usize>(&self) -> Option<StringWrapper<[u8; M]>> {
+        let mut folded = StringWrapper::new([0_u8; M]);
+        for c in self.chars().flat_map(char::to_lowercase) {
+            folded.push(c).ok()?;
+        }
+        Some(folded)
+    }
+
+    /// Return a copy of the string converted to upper case with `char::to_uppercase`, in a new
+    /// `T2` buffer.
     ///
-    /// ```
-    /// use string_wrapper::StringWrapper;
+    /// Upper-casing can make the string longer in bytes, e.g. `'ß'` becomes `"SS"`, hence the
+    /// separate buffer type.
     ///
-    /// let sw: Option<StringWrapper<[u8; 3]>> = StringWrapper::from_str_safe("foo");
-    /// assert_eq!(format!("{}", sw.unwrap()), "foo");
-    /// ```
+    /// # Errors
+    /// Return `Error::InsufficientLength` if the result does not fit in `T2`. `expected` is the
+    /// length the whole result would have needed.
+    pub fn to_uppercase_into<T2: OwnedBuffer>(&self) -> Result<StringWrapper<T2>, Error> {
+        self.map_chars_into(char::to_uppercase)
+    }
+
+    /// Return a copy of the string converted to lower case with `char::to_lowercase`, in a new
+    /// `T2` buffer.
     ///
-    /// ```
-    /// use string_wrapper::StringWrapper;
+    /// Lower-casing can make the string longer in bytes, e.g. `'İ'` becomes `"i\u{307}"`.
     ///
-    /// let sw: Option<StringWrapper<[u8; 3]>> = StringWrapper::from_str_safe("foobar");
-    /// assert_eq!(sw, None);
-    /// ```
-    #[must_use]
-    pub fn from_str_safe(s: &str) -> Option<StringWrapper<T>> {
-        let buffer = T::new();
-        let mut sw = StringWrapper::new(buffer);
-        match sw.push_partial_str(s) {
-            Ok(_) => Some(sw),
-            Err(_) => None,
-        }
+    /// # Errors
+    /// Return `Error::InsufficientLength` if the result does not fit in `T2`. `expected` is the
+    /// length the whole result would have needed.
+    pub fn to_lowercase_into<T2: OwnedBuffer>(&self) -> Result<StringWrapper<T2>, Error> {
+        self.map_chars_into(char::to_lowercase)
     }
-}
-
-fn starts_well_formed_utf8_sequence(byte: u8) -> bool {
-    // ASCII byte or "leading" byte
-    !(128..192).contains(&byte)
-}
 
-// FIXME: Use `std::slice::bytes::copy_memory` instead when it’s stable.
-/// Copies data from `src` to `dst`
-///
-/// Panics if the length of `dst` is less than the length of `src`.
-fn copy_memory(src: &[u8], dst: &mut [u8]) {
-    let len_src = src.len();
-    assert!(dst.len() >= len_src);
-    // `dst` is unaliasable, so we know statically it doesn't overlap
-    // with `src`.
-    unsafe {
-        ptr::copy_nonoverlapping(src.as_ptr(), dst.as_mut_ptr(), len_src);
+    fn map_chars_into<T2, F, I>(&self, f: F) -> Result<StringWrapper<T2>, Error>
+    where
+        T2: OwnedBuffer,
+        F: FnMut(char) -> I,
+        I: Iterator<Item = char>,
+    {
+        let mut mapped = StringWrapper::new(T2::new());
+        let mut appender = Appender::new(&mut mapped);
+        for c in self.chars().flat_map(f) {
+            appender.push_str(c.encode_utf8(&mut [0; 4]));
+        }
+        appender.finish()?;
+        Ok(mapped)
     }
-}
-
-impl<T> ops::Deref for StringWrapper<T>
-where
-    T: Buffer,
-{
-    type Target = str;
 
-    fn deref(&self) -> &str {
-        unsafe { str::from_utf8_unchecked(&self.buffer.as_ref()[..self.len]) }
+    /// Return whether the string contains `needle`.
+    pub fn contains_str(&self, needle: &str) -> bool {
+        (**self).contains(needle)
     }
-}
 
-impl<T> ops::DerefMut for StringWrapper<T>
-where
-    T: Buffer,
-{
-    fn deref_mut(&mut self) -> &mut str {
-        unsafe { str::from_utf8_unchecked_mut(&mut self.buffer.as_mut()[..self.len]) }
+    /// Return whether the string contains the code point `c`.
+    pub fn contains_char(&self, c: char) -> bool {
+        (**self).contains(c)
     }
-}
 
-impl<T> core::borrow::Borrow<str> for StringWrapper<T>
-where
-    T: Buffer,
-{
-    fn borrow(&self) -> &str {
-        self
+    /// Return whether the string starts with `prefix`.
+    pub fn starts_with_str(&self, prefix: &str) -> bool {
+        (**self).starts_with(prefix)
     }
-}
 
-impl<T> fmt::Display for StringWrapper<T>
-where
-    T: Buffer,
-{
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        fmt::Display::fmt(&**self, f)
+    /// Return whether the string ends with `suffix`.
+    pub fn ends_with_str(&self, suffix: &str) -> bool {
+        (**self).ends_with(suffix)
     }
-}
 
-impl<T> fmt::Debug for StringWrapper<T>
-where
-    T: Buffer,
-{
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        fmt::Debug::fmt(&**self, f)
+    /// Return the byte offset of the first occurrence of `needle`, if any.
+    pub fn find_str(&self, needle: &str) -> Option<usize> {
+        (**self).find(needle)
     }
-}
 
-impl<T: Buffer> PartialEq for StringWrapper<T> {
-    fn eq(&self, other: &Self) -> bool {
-        **self == **other
+    /// Return the byte offset of the first occurrence of `c`, if any.
+    pub fn find_char(&self, c: char) -> Option<usize> {
+        (**self).find(c)
     }
-}
 
-// We need to explicitly define Eq here, because the derive logic only impls it when T is also Eq.
-impl<T: Buffer> Eq for StringWrapper<T> {}
+    /// Return the byte offset of the last occurrence of `needle`, if any.
+    pub fn rfind_str(&self, needle: &str) -> Option<usize> {
+        (**self).rfind(needle)
+    }
 
-impl<T: Buffer> PartialOrd for StringWrapper<T> {
-    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
-        Some(self.cmp(other))
+    /// Return the byte offset of the last occurrence of `c`, if any.
+    pub fn rfind_char(&self, c: char) -> Option<usize> {
+        (**self).rfind(c)
     }
-}
 
-impl<T: Buffer> hash::Hash for StringWrapper<T> {
-    fn hash<H: hash::Hasher>(&self, state: &mut H) {
-        (**self).hash(state);
+    /// Retain only the ASCII bytes for which `f` returns `true`, removing the others in place.
+    ///
+    /// Non-ASCII bytes (`>= 0x80`) are always kept and never passed to `f`, so that the string
+    /// remains well-formed UTF-8. This avoids decoding code points, which makes it faster than
+    /// filtering `chars()` when only ASCII matters.
+    pub fn retain_ascii<F: FnMut(u8) -> bool>(&mut self, mut f: F) {
+        let len = self.len;
+        let bytes = self.buffer.as_mut();
+        let mut kept = 0;
+        for i in 0..len {
+            let b = bytes[i];
+            if !b.is_ascii() || f(b) {
+                bytes[kept] = b;
+                kept += 1;
+            }
+        }
+        self.len = kept;
     }
-}
 
-impl<T: Buffer> Ord for StringWrapper<T> {
-    fn cmp(&self, other: &Self) -> cmp::Ordering {
-        (**self).cmp(&**other)
+    /// Return the number of code points in the string.
+    ///
+    /// Unlike `len()`, which is in bytes, this has to walk the whole string.
+    pub fn char_count(&self) -> usize {
+        self.chars().count()
     }
-}
 
-impl<T: Buffer> fmt::Write for StringWrapper<T> {
-    fn write_str(&mut self, s: &str) -> fmt::Result {
-        let res = self.push_str(s);
-        if res.is_err() {
-            Err(fmt::Error)
-        } else {
+    /// Check that the string can grow by `additional` bytes.
+    ///
+    /// # Errors
+    /// Return `Error::InsufficientLength` if the extra capacity is less than `additional`.
+    pub fn ensure_capacity(&self, additional: usize) -> Result<(), Error> {
+        if self.extra_capacity() >= additional {
             Ok(())
+        } else {
+            Err(Error::InsufficientLength {
+                expected: self.len.saturating_add(additional),
+                actual: self.capacity(),
+            })
         }
     }
-}
 
-#[cfg(feature = "use_serde")]
-impl<T: Buffer> serde::Serialize for StringWrapper<T> {
-    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
-        self.to_string().serialize(serializer)
+    /// Append formatted output to the string, reporting overflow as an `Error` rather than the
+    /// opaque `fmt::Error`. See also the `append_fmt!` macro.
+    ///
+    /// # Errors
+    /// Return `Error::InsufficientLength` with the string unchanged if the extra capacity is not
+    /// sufficient. `expected` is the length the whole output would have needed.
+    pub fn try_format(&mut self, args: fmt::Arguments) -> Result<(), Error> {
+        let mut appender = Appender::new(self);
+        // `Appender` never fails, it records overflow for `finish` instead.
+        let _ = fmt::Write::write_fmt(&mut appender, args);
+        appender.finish()
     }
-}
 
-#[cfg(feature = "use_serde")]
-impl<'de, T: OwnedBuffer> serde::Deserialize<'de> for StringWrapper<T> {
-    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
-        let s: String = serde::Deserialize::deserialize(deserializer)?;
-        let sb = StringWrapper::from_str_safe(&s).ok_or_else(|| {
-            let buff = T::new();
-            let msg: String = format!("string that can fit into {} bytes", buff.as_ref().len());
+    /// Append a code point to the string if the extra capacity is sufficient.
+    ///
+    /// Return whether `c` was appended; the string is unchanged if it wasn't.
+    pub fn push_char_lossy(&mut self, c: char) -> bool {
+        self.push(c).is_ok()
+    }
 
-            use serde::de::Error;
+    /// Return the slice of unused bytes after the string, to be filled before `advance_len`.
+    ///
+    /// This is the same as `extra_bytes_mut`, named after `Vec::spare_capacity_mut`. It is safe
+    /// since bytes past `len()` are not part of the string; the obligation to write valid UTF-8
+    /// lies with `advance_len`.
+    pub fn spare_capacity_mut(&mut self) -> &mut [u8] {
+        self.extra_bytes_mut()
+    }
 
-            D::Error::invalid_length(s.len(), &StringExpected(msg))
-        })?;
-        Ok(sb)
+    /// Grow the string by `n` bytes that were written into `spare_capacity_mut`.
+    ///
+    /// # Safety
+    /// Users must ensure that the first `n` bytes of `spare_capacity_mut` are well-formed UTF-8.
+    ///
+    /// # Panics
+    /// Panics if `n` is greater than the extra capacity.
+    pub unsafe fn advance_len(&mut self, n: usize) {
+        assert!(n <= self.extra_capacity());
+        self.len += n;
     }
-}
 
-// It seems silly that I can't just pass a String to invalid_length, but there's no implementation
-// of Expected for String, so...
-#[cfg(feature = "use_serde")]
-struct StringExpected(String);
-#[cfg(feature = "use_serde")]
-impl serde::de::Expected for StringExpected {
-    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-        fmt::Display::fmt(&self.0, formatter)
+    /// Return an iterator over the code points of the string.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::str::FromStr;
+    /// use string_wrapper::StringWrapper;
+    ///
+    /// let sw: StringWrapper<[u8; 8]> = StringWrapper::from_str("aé☃").unwrap();
+    /// let chars: Vec<char> = sw.chars().collect();
+    /// assert_eq!(chars, ['a', 'é', '☃']);
+    /// ```
+    pub fn chars(&self) -> str::Chars<'_> {
+        (**self).chars()
     }
-}
 
-unsafe impl<'a, T: ?Sized + Buffer> Buffer for &'a mut T {
-    fn as_ref(&self) -> &[u8] {
-        (**self).as_ref()
+    /// Return an iterator over the code points of the string and their byte offsets.
+    pub fn char_indices(&self) -> str::CharIndices<'_> {
+        (**self).char_indices()
     }
-    fn as_mut(&mut self) -> &mut [u8] {
-        (**self).as_mut()
+
+    /// Return an iterator over the bytes of the string.
+    pub fn bytes(&self) -> str::Bytes<'_> {
+        (**self).bytes()
     }
-}
 
-unsafe impl<T: ?Sized + Buffer> Buffer for Box<T> {
-    fn as_ref(&self) -> &[u8] {
-        (**self).as_ref()
+    /// Remove leading and trailing whitespace in place.
+    pub fn trim_in_place(&mut self) {
+        self.trim_end_in_place();
+        self.trim_start_in_place();
     }
-    fn as_mut(&mut self) -> &mut [u8] {
-        (**self).as_mut()
+
+    /// Remove leading whitespace in place, moving the rest of the string to the front.
+    pub fn trim_start_in_place(&mut self) {
+        let start = self.len - self.trim_start().len();
+        self.shift_left(start);
     }
-}
 
-unsafe impl Buffer for Vec<u8> {
-    fn as_ref(&self) -> &[u8] {
-        self
+    /// Remove trailing whitespace in place.
+    pub fn trim_end_in_place(&mut self) {
+        self.len = self.trim_end().len();
     }
-    fn as_mut(&mut self) -> &mut [u8] {
-        self
+
+    /// Append each item of `iter`, separated by `sep`.
+    ///
+    /// # Errors
+    /// Return `Error::InsufficientLength` at the first item that doesn't fit along with its
+    /// separator. The items appended before it are kept, but neither that item nor its separator
+    /// are partially written.
+    pub fn push_join<I, S>(&mut self, iter: I, sep: &str) -> Result<(), Error>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        for (i, item) in iter.into_iter().enumerate() {
+            let sep = if i > 0 { sep } else { "" };
+            let item = item.as_ref();
+            self.ensure_capacity(sep.len().saturating_add(item.len()))?;
+            self.push_str(sep).unwrap();
+            self.push_str(item).unwrap();
+        }
+        Ok(())
     }
-}
 
-unsafe impl Buffer for [u8] {
-    fn as_ref(&self) -> &[u8] {
-        self
+    /// Copy the string into a new buffer of another type, e.g. a smaller array once the string
+    /// has been trimmed.
+    ///
+    /// This is a copy, `self` is left untouched.
+    ///
+    /// # Errors
+    /// Return `Error::InsufficientLength` if the string doesn't fit in a `U`.
+    pub fn reinto<U: OwnedBuffer>(&self) -> Result<StringWrapper<U>, Error> {
+        StringWrapper::copy_from_str(U::new(), self)
     }
-    fn as_mut(&mut self) -> &mut [u8] {
-        self
+
+    /// Append code points from `iter` until the next one doesn't fit, and return how many were
+    /// appended.
+    ///
+    /// The code point that didn't fit is left in `iter`, so it can be continued e.g. into another
+    /// string.
+    pub fn push_chars<I: Iterator<Item = char>>(&mut self, iter: &mut Peekable<I>) -> usize {
+        let mut pushed = 0;
+        while let Some(&c) = iter.peek() {
+            if self.push(c).is_err() {
+                break;
+            }
+            iter.next();
+            pushed += 1;
+        }
+        pushed
     }
-}
 
-unsafe impl<const N: usize> Buffer for [u8; N] {
-    fn as_ref(&self) -> &[u8] {
-        self
+    /// Consume the string and copy it into a heap-allocated `String`.
+    ///
+    /// Unlike `to_string`, this doesn't go through `Display`.
+    pub fn into_string(self) -> String {
+        String::from(&*self)
     }
 
-    fn as_mut(&mut self) -> &mut [u8] {
-        self
+    /// Append `n` copies of the code point `c`, e.g. for padding.
+    ///
+    /// # Errors
+    /// Return `Error::InsufficientLength` with the string unchanged if the extra capacity is not
+    /// sufficient for all `n` copies.
+    pub fn repeat_char(&mut self, c: char, n: usize) -> Result<(), Error> {
+        let total = c.len_utf8().saturating_mul(n);
+        self.ensure_capacity(total)?;
+        fill_char(&mut self.extra_bytes_mut()[..total], c);
+        self.len += total;
+        Ok(())
     }
-}
 
-impl<const N: usize> OwnedBuffer for [u8; N] {
-    fn new() -> Self { [0_u8; N] }
-}
+    /// Pad the string with `fill` to `width` code points, like the `{:<}`, `{:>}` and `{:^}`
+    /// format specifiers do. Strings already at least `width` code points long are unchanged.
+    ///
+    /// # Errors
+    /// Return `Error::InsufficientLength` with the string unchanged if the extra capacity is not
+    /// sufficient for the padding.
+    pub fn pad_to(&mut self, width: usize, fill: char, align: Alignment) -> Result<(), Error> {
+        let padding = width.saturating_sub(self.char_count());
+        let before = match align {
+            Alignment::Left => 0,
+            Alignment::Right => padding,
+            Alignment::Center => padding / 2,
+        };
+        let fill_len = fill.len_utf8();
+        self.ensure_capacity(padding.saturating_mul(fill_len))?;
 
-/// Like a `format!` but uses a fixed size buffer of specified length
-#[macro_export]
-macro_rules! stack_format {
-    ($limit:literal, $($args:tt)*) => {
-        {
-            use std::fmt::Write;
-            let mut sw = $crate::StringWrapper::new([0u8; $limit]);
-            let r = write!(sw, $($args)*);
-            r.map(|_: ()| sw)
-        }
-    };
-}
+        let len = self.len;
+        let before_len = before * fill_len;
+        let bytes = self.buffer.as_mut();
+        bytes.copy_within(..len, before_len);
+        fill_char(&mut bytes[..before_len], fill);
+        self.len += before_len;
+        self.repeat_char(fill, padding - before)
+    }
 
-#[cfg(test)]
-#[allow(clippy::non_ascii_literal)]
-mod tests {
-    use Error;
-    use std;
+    /// Return whether the string is equal to `other`, ignoring ASCII case.
+    ///
+    /// Other `StringWrapper`s can be compared too, as `&other` derefs to `&str`.
+    pub fn eq_ignore_ascii_case(&self, other: &str) -> bool {
+        (**self).eq_ignore_ascii_case(other)
+    }
+
+    /// Return whether the string has the same content as `other`, which may use a different
+    /// buffer type, e.g. a `StringWrapper<[u8; 8]>` and a `StringWrapper<[u8; 16]>`.
+    ///
+    /// `==` only compares `StringWrapper`s of the same type, so that the compiler can infer the
+    /// type of the other side.
+    pub fn eq_content<U: Buffer>(&self, other: &StringWrapper<U>) -> bool {
+        **self == **other
+    }
+
+    /// Compare the string's content with `other`'s, which may use a different buffer type.
+    pub fn cmp_content<U: Buffer>(&self, other: &StringWrapper<U>) -> cmp::Ordering {
+        (**self).cmp(&**other)
+    }
+
+    /// Return an iterator over the pieces of the string separated by `sep`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::str::FromStr;
+    /// use string_wrapper::StringWrapper;
+    ///
+    /// let sw: StringWrapper<[u8; 8]> = StringWrapper::from_str("a,b,c").unwrap();
+    /// let pieces: Vec<&str> = sw.split_char(',').collect();
+    /// assert_eq!(pieces, ["a", "b", "c"]);
+    /// ```
+    pub fn split_char(&self, sep: char) -> str::Split<'_, char> {
+        (**self).split(sep)
+    }
+
+    /// Return an iterator over the pieces of the string separated by `sep`.
+    pub fn split_str<'a>(&'a self, sep: &'a str) -> str::Split<'a, &'a str> {
+        (**self).split(sep)
+    }
+
+    /// Return an iterator over the lines of the string.
+    pub fn lines(&self) -> str::Lines<'_> {
+        (**self).lines()
+    }
+
+    /// Append `line` followed by a `'\n'`.
+    ///
+    /// # Errors
+    /// Return `Error::InsufficientLength` with the string unchanged if the extra capacity is not
+    /// sufficient for both the line and its newline.
+    pub fn push_line(&mut self, line: &str) -> Result<(), Error> {
+        self.ensure_capacity(line.len().saturating_add(1))?;
+        self.push_str(line).unwrap();
+        self.push('\n').unwrap();
+        Ok(())
+    }
+
+    /// Remove the last `n_bytes` bytes of the string and return them.
+    ///
+    /// The returned `&str` borrows the bytes past the new end of the string, which is why the
+    /// string can't be modified while it's alive.
+    ///
+    /// # Panics
+    /// Panics if `n_bytes` > current length, or if it doesn't leave the string on a character
+    /// boundary.
+    pub fn pop_str(&mut self, n_bytes: usize) -> &str {
+        assert!(n_bytes <= self.len);
+        let old_len = self.len;
+        let new_len = old_len - n_bytes;
+        assert!((**self).is_char_boundary(new_len));
+        self.len = new_len;
+        // The popped bytes were the end of a well-formed string, starting at a char boundary.
+        unsafe { str::from_utf8_unchecked(&self.buffer.as_ref()[new_len..old_len]) }
+    }
+
+    /// Write the string as its length in bytes, as a big-endian `u16`, followed by its bytes.
+    ///
+    /// See `from_wire` for the reverse operation.
+    ///
+    /// # Errors
+    /// Return an `InvalidInput` error if the string is longer than `u16::MAX` bytes, and forward
+    /// errors from `w`.
+    pub fn to_wire<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+        let len = u16::try_from(self.len).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "string too long for a u16 length",
+            )
+        })?;
+        w.write_all(&len.to_be_bytes())?;
+        w.write_all(self.as_bytes())
+    }
+
+    /// Encode the string as UTF-16 into `out`, and return the number of `u16` units written.
+    ///
+    /// # Errors
+    /// Return `Error::InsufficientLength` with `out` unchanged if it is too short, where
+    /// `expected` is the number of units needed.
+    pub fn encode_utf16_into(&self, out: &mut [u16]) -> Result<usize, Error> {
+        let needed = self.encode_utf16().count();
+        if needed > out.len() {
+            return Err(Error::InsufficientLength {
+                expected: needed,
+                actual: out.len(),
+            });
+        }
+        for (dst, unit) in out.iter_mut().zip(self.encode_utf16()) {
+            *dst = unit;
+        }
+        Ok(needed)
+    }
+
+    /// If the string starts with `prefix`, remove it in place and return `true`.
+    pub fn strip_prefix_in_place(&mut self, prefix: &str) -> bool {
+        if !self.starts_with(prefix) {
+            return false;
+        }
+        self.shift_left(prefix.len());
+        true
+    }
+
+    /// If the string ends with `suffix`, remove it in place and return `true`.
+    pub fn strip_suffix_in_place(&mut self, suffix: &str) -> bool {
+        if !self.ends_with(suffix) {
+            return false;
+        }
+        self.len -= suffix.len();
+        true
+    }
+
+    /// Split the string into consecutive frames that each fit in a `T2`.
+    ///
+    /// Frames are split on character boundaries, so a frame may be up to 3 bytes shorter than
+    /// the capacity of a `T2` rather than cut a code point in two.
+    ///
+    /// # Panics
+    /// The iterator panics if a code point doesn't fit in an empty `T2`.
+    pub fn split_into_frames<T2: OwnedBuffer>(
+        &self,
+    ) -> impl Iterator<Item = StringWrapper<T2>> + '_ {
+        let mut rest: &str = self;
+        iter::from_fn(move || {
+            if rest.is_empty() {
+                return None;
+            }
+            let mut frame = StringWrapper::new(T2::new());
+            let mut end = cmp::min(frame.capacity(), rest.len());
+            while !rest.is_char_boundary(end) {
+                end -= 1;
+            }
+            assert!(end > 0, "code point doesn't fit in an empty frame");
+            frame.push_str(&rest[..end]).unwrap();
+            rest = &rest[end..];
+            Some(frame)
+        })
+    }
+
+    /// Return the substring in the byte `range`, or `None` if it is out of bounds or not on
+    /// character boundaries.
+    pub fn get<R: ops::RangeBounds<usize>>(&self, range: R) -> Option<&str> {
+        let (start, end) = range_bounds(range, self.len)?;
+        (**self).get(start..end)
+    }
+
+    /// Return the mutable substring in the byte `range`, or `None` if it is out of bounds or not
+    /// on character boundaries.
+    pub fn get_mut<R: ops::RangeBounds<usize>>(&mut self, range: R) -> Option<&mut str> {
+        let (start, end) = range_bounds(range, self.len)?;
+        (**self).get_mut(start..end)
+    }
+
+    /// Return the first code point of the string, if any.
+    pub fn first_char(&self) -> Option<char> {
+        self.chars().next()
+    }
+
+    /// Return the last code point of the string, if any.
+    pub fn last_char(&self) -> Option<char> {
+        self.chars().next_back()
+    }
+
+    /// Let `f` modify the backing storage in place, given the current length, and set the length
+    /// to the one it returns.
+    ///
+    /// In debug builds, the new string is checked to be well-formed UTF-8. This check is skipped
+    /// in release builds, which is why this method is still `unsafe`.
+    ///
+    /// # Safety
+    /// Users must ensure that the first `new_len` bytes of the buffer are well-formed UTF-8 when
+    /// `f` returns `new_len`.
+    ///
+    /// # Panics
+    /// Panics if `f` returns a length greater than the capacity, or in debug builds, if the new
+    /// string is not well-formed UTF-8.
+    pub unsafe fn with_buffer_mut<F: FnOnce(&mut [u8], usize) -> usize>(&mut self, f: F) {
+        let new_len = f(self.buffer.as_mut(), self.len);
+        assert!(new_len <= self.capacity());
+        debug_assert!(str::from_utf8(&self.buffer.as_ref()[..new_len]).is_ok());
+        self.len = new_len;
+    }
+
+    /// Move the first `n` code points of the string to its end.
+    ///
+    /// # Panics
+    /// Panics if `n` is greater than the number of code points.
+    pub fn rotate_left_chars(&mut self, n: usize) {
+        let mid = self
+            .char_offset(n)
+            .expect("n is greater than the number of chars");
+        let len = self.len;
+        self.buffer.as_mut()[..len].rotate_left(mid);
+    }
+
+    /// Move the last `n` code points of the string to its start.
+    ///
+    /// # Panics
+    /// Panics if `n` is greater than the number of code points.
+    pub fn rotate_right_chars(&mut self, n: usize) {
+        let mid = match n {
+            0 => self.len,
+            _ => {
+                let (i, _) = self
+                    .char_indices()
+                    .rev()
+                    .nth(n - 1)
+                    .expect("n is greater than the number of chars");
+                i
+            }
+        };
+        let len = self.len;
+        self.buffer.as_mut()[..len].rotate_left(mid);
+    }
+
+    /// Return the byte offset of the `n`-th code point, or of the end of the string if it has
+    /// exactly `n` code points.
+    fn char_offset(&self, n: usize) -> Option<usize> {
+        self.char_indices()
+            .map(|(i, _)| i)
+            .chain(Some(self.len))
+            .nth(n)
+    }
+
+    /// Append bytes that are well-formed UTF-8 on their own.
+    ///
+    /// No attempt is made to complete a sequence that would have been split across calls: an
+    /// incomplete sequence at either end of `bytes` is rejected like any other malformed input.
+    ///
+    /// # Errors
+    /// Return `Error::InvalidUtf8` or `Error::InsufficientLength` with the string unchanged.
+    pub fn push_bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        let s = str::from_utf8(bytes).map_err(Error::InvalidUtf8)?;
+        self.push_str(s)
+    }
+
+    /// Replace each run of ASCII whitespace with a single space, in place.
+    pub fn dedup_whitespace(&mut self) {
+        let len = self.len;
+        let bytes = self.buffer.as_mut();
+        let mut kept = 0;
+        let mut in_run = false;
+        for i in 0..len {
+            let b = bytes[i];
+            if !b.is_ascii_whitespace() {
+                bytes[kept] = b;
+                kept += 1;
+            } else if !in_run {
+                bytes[kept] = b' ';
+                kept += 1;
+            }
+            in_run = b.is_ascii_whitespace();
+        }
+        self.len = kept;
+    }
+
+    /// Remove each code point for which `same(previous, current)` returns `true`, where
+    /// `previous` is the last code point kept, compacting the string in place. The first code
+    /// point of each run is kept.
+    pub fn dedup_by<F: FnMut(char, char) -> bool>(&mut self, mut same: F) {
+        let mut previous = None;
+        self.compact_chars(|c| match previous {
+            Some(p) if same(p, c) => false,
+            _ => {
+                previous = Some(c);
+                true
+            }
+        });
+    }
+
+    /// Keep only the code points for which `keep` returns `true`, in order, compacting the
+    /// string in place.
+    fn compact_chars<F: FnMut(char) -> bool>(&mut self, mut keep: F) {
+        let len = self.len;
+        let bytes = self.buffer.as_mut();
+        let mut read = 0;
+        let mut kept = 0;
+        while read < len {
+            // `read` is on a char boundary of the original string, and bytes from there on
+            // haven't been overwritten.
+            let c = unsafe { str::from_utf8_unchecked(&bytes[read..len]) }
+                .chars()
+                .next()
+                .unwrap();
+            let c_len = c.len_utf8();
+            if keep(c) {
+                bytes.copy_within(read..read + c_len, kept);
+                kept += c_len;
+            }
+            read += c_len;
+        }
+        self.len = kept;
+    }
+
+    /// Reverse the order of the code points of the string, in place.
+    pub fn reverse_in_place(&mut self) {
+        let len = self.len;
+        let bytes = &mut self.buffer.as_mut()[..len];
+        bytes.reverse();
+        // Each multi-byte sequence is now backwards, with its continuation bytes first: put them
+        // back in order.
+        let mut start = 0;
+        while start < len {
+            let mut end = start;
+            while !starts_well_formed_utf8_sequence(bytes[end]) {
+                end += 1;
+            }
+            bytes[start..=end].reverse();
+            start = end + 1;
+        }
+    }
+
+    /// Return the number of non-overlapping occurrences of `pat`.
+    ///
+    /// Matches are counted from the start, so `"aaaa"` contains `"aa"` twice, not three times.
+    pub fn count_matches(&self, pat: &str) -> usize {
+        (**self).matches(pat).count()
+    }
+
+    /// Return an iterator over the non-overlapping occurrences of `pat` and their byte offsets.
+    pub fn match_indices_str<'a>(&'a self, pat: &'a str) -> str::MatchIndices<'a, &'a str> {
+        (**self).match_indices(pat)
+    }
+
+    /// Append a string slice, evicting existing content from the front to make room.
+    ///
+    /// If `s` fits in the extra capacity, this is the same as `push_str`. Otherwise the fewest
+    /// whole code points needed are removed from the start of the string (shifting the rest left)
+    /// before `s` is appended. If `s` is longer than the whole capacity, the string is emptied
+    /// and only the longest suffix of `s` that fits and starts on a code point boundary is kept.
+    ///
+    /// Either way the most recent content is kept, and the string may end up a few bytes short of
+    /// its capacity when the cut falls inside a multi-byte code point.
+    pub fn push_str_sliding(&mut self, s: &str) {
+        let capacity = self.capacity();
+        let s = if s.len() > capacity {
+            self.len = 0;
+            let mut start = s.len() - capacity;
+            while !s.is_char_boundary(start) {
+                start += 1;
+            }
+            &s[start..]
+        } else {
+            s
+        };
+        if s.len() > self.extra_capacity() {
+            // `s` fits in the whole capacity, so `self.len >= evict` and the loop stops at the
+            // end of the string at the latest.
+            let mut evict = s.len() - self.extra_capacity();
+            while !self.is_char_boundary(evict) {
+                evict += 1;
+            }
+            self.shift_left(evict);
+        }
+        self.push_str(s).unwrap();
+    }
+
+    /// Return an iterator over the overlapping windows of `n` consecutive code points.
+    ///
+    /// The windows borrow the string. There are none if it has fewer than `n` code points.
+    ///
+    /// # Panics
+    /// Panics if `n` is 0.
+    pub fn char_ngrams(&self, n: usize) -> impl Iterator<Item = &str> {
+        assert!(n > 0, "n-grams must have at least one code point");
+        let s: &str = self;
+        self.char_boundaries()
+            .zip(self.char_boundaries().skip(n))
+            .map(move |(start, end)| &s[start..end])
+    }
+
+    /// Replace every occurrence of `from` with `to`, in place.
+    ///
+    /// # Errors
+    /// Return `Error::CharLengthMismatch` with the string unchanged if `from` and `to` don't have
+    /// the same UTF-8 length.
+    pub fn replace_char_in_place(&mut self, from: char, to: char) -> Result<(), Error> {
+        if from.len_utf8() != to.len_utf8() {
+            return Err(Error::CharLengthMismatch {
+                from: from.len_utf8(),
+                to: to.len_utf8(),
+            });
+        }
+        let mut to_utf8 = [0; 4];
+        let to_utf8 = to.encode_utf8(&mut to_utf8).as_bytes();
+        let mut start = 0;
+        while let Some(found) = self[start..].find(from) {
+            let found = start + found;
+            start = found + to_utf8.len();
+            // Overwriting a code point with another of the same length keeps the string valid.
+            self.buffer.as_mut()[found..start].copy_from_slice(to_utf8);
+        }
+        Ok(())
+    }
+
+    /// Return a read-only view of the string.
+    pub fn as_read_only(&self) -> ReadOnlyStringWrapper<'_> {
+        ReadOnlyStringWrapper { s: self }
+    }
+
+    /// Return a pointer to the first byte of the string, e.g. to pass it to C along with `len()`.
+    ///
+    /// The string is not NUL-terminated; use `as_cstr` for that.
+    pub fn as_ptr(&self) -> *const u8 {
+        self.buffer.as_ref().as_ptr()
+    }
+
+    /// Return a mutable pointer to the first byte of the string.
+    ///
+    /// The string is not NUL-terminated; use `as_cstr` for that. Writing through the pointer is
+    /// subject to the same rules as `buffer_mut`: the first `len()` bytes must stay well-formed
+    /// UTF-8.
+    pub fn as_mut_ptr(&mut self) -> *mut u8 {
+        self.buffer.as_mut().as_mut_ptr()
+    }
+
+    /// Return a NUL-terminated view of the string, e.g. to pass it to C, without allocating.
+    ///
+    /// The terminator is written to the byte just after the string, in the extra capacity, and
+    /// `len()` is unchanged.
+    ///
+    /// # Errors
+    /// Return `Error::InsufficientLength` if there is no extra capacity for the terminator, or
+    /// `Error::InteriorNul` if the string itself contains a NUL byte.
+    pub fn as_cstr(&mut self) -> Result<&core::ffi::CStr, Error> {
+        let len = self.len;
+        if len == self.capacity() {
+            return Err(Error::InsufficientLength {
+                expected: len + 1,
+                actual: self.capacity(),
+            });
+        }
+        if let Some(position) = self.bytes().position(|b| b == 0) {
+            return Err(Error::InteriorNul { position });
+        }
+        let bytes = &mut self.buffer.as_mut()[..=len];
+        bytes[len] = 0;
+        // We just checked that the only NUL is the terminator.
+        Ok(unsafe { core::ffi::CStr::from_bytes_with_nul_unchecked(bytes) })
+    }
+
+    /// Append as much of `s` as fits, cut on a code point boundary, and return whether all of it
+    /// fit.
+    ///
+    /// This is `push_partial_str(s).is_ok()`, for callers that only need to know whether the
+    /// string was truncated, e.g. to add an ellipsis.
+    pub fn saturating_push_str(&mut self, s: &str) -> bool {
+        self.push_partial_str(s).is_ok()
+    }
+
+    /// If the string has more than `max_chars` code points, shorten it in place to `max_chars - 1`
+    /// code points followed by `'…'` (U+2026).
+    ///
+    /// The ellipsis takes 3 bytes, so if the shortened string plus the ellipsis doesn't fit in the
+    /// capacity, more code points are removed until it does. The string is emptied if `max_chars`
+    /// is 0 or the capacity is too small for the ellipsis alone.
+    pub fn ellipsize(&mut self, max_chars: usize) {
+        const ELLIPSIS: char = '…';
+        match self.char_offset(max_chars) {
+            Some(end) if end < self.len => {}
+            _ => return,
+        }
+        if max_chars == 0 || self.capacity() < ELLIPSIS.len_utf8() {
+            self.len = 0;
+            return;
+        }
+        let limit = self.capacity() - ELLIPSIS.len_utf8();
+        let mut end = self.char_offset(max_chars - 1).unwrap();
+        if end > limit {
+            end = limit;
+            while !self.is_char_boundary(end) {
+                end -= 1;
+            }
+        }
+        self.len = end;
+        self.push(ELLIPSIS).unwrap();
+    }
+
+    /// Remove the first `n_bytes` bytes of the string, moving the rest to the front.
+    ///
+    /// # Panics
+    /// Panics if `n_bytes` is greater than `len()` or not on a code point boundary.
+    pub fn shift_left(&mut self, n_bytes: usize) {
+        assert!(self.is_char_boundary(n_bytes));
+        let len = self.len;
+        self.buffer.as_mut().copy_within(n_bytes..len, 0);
+        self.len -= n_bytes;
+    }
+
+    /// Shorten the string to its first `max` extended grapheme clusters, if it has more.
+    ///
+    /// Unlike cutting on a code point boundary, this never splits what is displayed as a single
+    /// character, such as an emoji ZWJ sequence or a letter followed by combining marks.
+    #[cfg(feature = "use_unicode_segmentation")]
+    pub fn truncate_graphemes(&mut self, max: usize) {
+        use unicode_segmentation::UnicodeSegmentation;
+        if let Some((end, _)) = self.grapheme_indices(true).nth(max) {
+            self.len = end;
+        }
+    }
+
+    /// Shorten the string to `new_len` bytes, like `truncate` but without panicking.
+    ///
+    /// # Errors
+    /// Return `Error::NotCharBoundary` with the string unchanged if `new_len` is greater than
+    /// `len()` or not on a code point boundary.
+    pub fn try_truncate(&mut self, new_len: usize) -> Result<(), Error> {
+        if !self.is_char_boundary(new_len) {
+            return Err(Error::NotCharBoundary { index: new_len });
+        }
+        self.len = new_len;
+        Ok(())
+    }
+
+    /// Return an iterator over the whitespace-separated words of the string.
+    pub fn split_whitespace(&self) -> str::SplitWhitespace<'_> {
+        (**self).split_whitespace()
+    }
+
+    /// Return the number of whitespace-separated words in the string.
+    pub fn word_count(&self) -> usize {
+        self.split_whitespace().count()
+    }
+
+    /// Insert a string slice at byte offset `idx`, moving the rest of the string to make room.
+    ///
+    /// # Errors
+    /// Return `Error::InsufficientLength` with the string unchanged if the extra capacity is not
+    /// sufficient.
+    ///
+    /// # Panics
+    /// Panics if `idx` is greater than `len()` or not on a code point boundary.
+    pub fn insert_str(&mut self, idx: usize, s: &str) -> Result<(), Error> {
+        assert!(self.is_char_boundary(idx));
+        self.ensure_capacity(s.len())?;
+        let len = self.len;
+        let bytes = self.buffer.as_mut();
+        bytes.copy_within(idx..len, idx + s.len());
+        bytes[idx..idx + s.len()].copy_from_slice(s.as_bytes());
+        self.len += s.len();
+        Ok(())
+    }
+
+    /// Return a `fmt::Write` adapter inserting at byte offset `idx` instead of appending.
+    ///
+    /// ```
+    /// use std::fmt::Write;
+    /// use std::str::FromStr;
+    /// use string_wrapper::StringWrapper;
+    ///
+    /// let mut s: StringWrapper<[u8; 16]> = StringWrapper::from_str("x = ;").unwrap();
+    /// write!(s.writer_at(4), "{}", 42).unwrap();
+    /// assert_eq!(&*s, "x = 42;");
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if `idx` is greater than `len()` or not on a code point boundary.
+    pub fn writer_at(&mut self, idx: usize) -> Writer<'_, T> {
+        assert!(self.is_char_boundary(idx));
+        Writer {
+            sw: self,
+            start: idx,
+        }
+    }
+
+    /// Return whether `index` is 0, `len()`, or the offset of the start of a code point, i.e. a
+    /// valid argument for `truncate` or `shift_left`.
+    pub fn is_char_boundary(&self, index: usize) -> bool {
+        (**self).is_char_boundary(index)
+    }
+
+    /// Append all of `parts`, checking the capacity once for their total length.
+    ///
+    /// # Errors
+    /// Return `Error::InsufficientLength` with the string unchanged if the extra capacity is not
+    /// sufficient for all of them together.
+    pub fn push_str_all(&mut self, parts: &[&str]) -> Result<(), Error> {
+        let total = parts
+            .iter()
+            .fold(0_usize, |total, part| total.saturating_add(part.len()));
+        self.ensure_capacity(total)?;
+        let len = self.len;
+        let mut rest = &mut self.buffer.as_mut()[len..len + total];
+        for part in parts {
+            let (head, tail) = rest.split_at_mut(part.len());
+            head.copy_from_slice(part.as_bytes());
+            rest = tail;
+        }
+        self.len += total;
+        Ok(())
+    }
+
+    /// Keep only the substring in the byte `range`, moving it to the front of the buffer.
+    ///
+    /// # Panics
+    /// Panics if the range is out of bounds, decreasing, or not on code point boundaries.
+    pub fn keep_range<R: ops::RangeBounds<usize>>(&mut self, range: R) {
+        let (start, end) = range_bounds(range, self.len).expect("range out of bounds");
+        assert!(start <= end && self.is_char_boundary(start) && self.is_char_boundary(end));
+        self.len = end;
+        self.shift_left(start);
+    }
+
+    /// Append a string slice, panicking if it doesn't fit, e.g. in tests or setup code where
+    /// overflow is a bug.
+    ///
+    /// # Panics
+    /// Panics if the extra capacity is not sufficient, with a message giving the bytes needed and
+    /// available.
+    pub fn push_str_expect(&mut self, s: &str) {
+        if self.push_str(s).is_err() {
+            panic!(
+                "push_str_expect: {} bytes needed but only {} available",
+                s.len(),
+                self.extra_capacity()
+            );
+        }
+    }
+
+    /// Exchange the contents of two strings, e.g. to flip double buffers.
+    ///
+    /// This swaps the buffers themselves: cheap for e.g. `Vec<u8>`, but a copy of the whole
+    /// capacity for arrays.
+    pub fn swap(&mut self, other: &mut StringWrapper<T>) {
+        mem::swap(self, other);
+    }
+
+    /// Return the code point starting at byte offset `byte_index`, or `None` if it is out of range
+    /// or not on a code point boundary.
+    pub fn char_at(&self, byte_index: usize) -> Option<char> {
+        self.get(byte_index..)?.chars().next()
+    }
+
+    /// Return the length in bytes of the code point starting at byte offset `byte_index`, or
+    /// `None` if it is out of range or not on a code point boundary.
+    pub fn char_len_at(&self, byte_index: usize) -> Option<usize> {
+        self.char_at(byte_index).map(char::len_utf8)
+    }
+
+    /// Return the last code point boundary before byte offset `byte_index`, e.g. to move a cursor
+    /// back by one code point, or `None` if `byte_index` is 0 or past the end of the string.
+    pub fn prev_char_boundary(&self, byte_index: usize) -> Option<usize> {
+        if byte_index == 0 || byte_index > self.len {
+            return None;
+        }
+        let mut i = byte_index - 1;
+        while !self.is_char_boundary(i) {
+            i -= 1;
+        }
+        Some(i)
+    }
+
+    /// Return the first code point boundary after byte offset `byte_index`, e.g. to move a cursor
+    /// forward by one code point, or `None` if `byte_index` is at or past the end of the string.
+    pub fn next_char_boundary(&self, byte_index: usize) -> Option<usize> {
+        if byte_index >= self.len {
+            return None;
+        }
+        let mut i = byte_index + 1;
+        while !self.is_char_boundary(i) {
+            i += 1;
+        }
+        Some(i)
+    }
+
+    /// Keep only the code points for which `f(index, c)` returns `true`, compacting the string in
+    /// place. `index` counts code points, not bytes, from 0.
+    pub fn retain_indexed<F: FnMut(usize, char) -> bool>(&mut self, mut f: F) {
+        let mut index = 0;
+        self.compact_chars(|c| {
+            index += 1;
+            f(index - 1, c)
+        });
+    }
+
+    /// Parse the string into another type, like `str::parse`.
+    ///
+    /// ```
+    /// use std::str::FromStr;
+    /// use string_wrapper::StringWrapper;
+    ///
+    /// let n: StringWrapper<[u8; 8]> = StringWrapper::from_str("42").unwrap();
+    /// assert_eq!(n.parse::<u32>(), Ok(42));
+    /// let x: StringWrapper<[u8; 8]> = StringWrapper::from_str("-2.5").unwrap();
+    /// assert_eq!(x.parse::<f64>(), Ok(-2.5));
+    /// assert!(x.parse::<u32>().is_err());
+    /// ```
+    ///
+    /// # Errors
+    /// Return `F::Err` if the string doesn't parse as an `F`.
+    pub fn parse<F: FromStr>(&self) -> Result<F, F::Err> {
+        (**self).parse()
+    }
+
+    /// Replace each ASCII byte `b` of the string with `f(b)`, in place, if the result is ASCII
+    /// too.
+    ///
+    /// Non-ASCII bytes are not passed to `f`, and a non-ASCII result is discarded, leaving the
+    /// byte unchanged. So the string always stays well-formed UTF-8 with the same length.
+    pub fn map_ascii_bytes<F: FnMut(u8) -> u8>(&mut self, mut f: F) {
+        let len = self.len;
+        for b in &mut self.buffer.as_mut()[..len] {
+            if b.is_ascii() {
+                let mapped = f(*b);
+                if mapped.is_ascii() {
+                    *b = mapped;
+                }
+            }
+        }
+    }
+
+    /// Repeat the current content of the string to fill the extra capacity, e.g. for placeholder
+    /// text.
+    ///
+    /// The last repetition is cut on a code point boundary if it doesn't fit whole, so a few bytes
+    /// of capacity may be left over. An empty string is left unchanged.
+    pub fn fill_repeating(&mut self) {
+        let pattern_len = self.len;
+        if pattern_len == 0 {
+            return;
+        }
+        while self.extra_capacity() >= pattern_len {
+            let len = self.len;
+            self.buffer.as_mut().copy_within(..pattern_len, len);
+            self.len += pattern_len;
+        }
+        // The string starts with the pattern, so its boundaries below `pattern_len` are the
+        // pattern's.
+        let mut tail_len = self.extra_capacity();
+        while !self.is_char_boundary(tail_len) {
+            tail_len -= 1;
+        }
+        let len = self.len;
+        self.buffer.as_mut().copy_within(..tail_len, len);
+        self.len += tail_len;
+    }
+
+    /// Return an iterator over the code point boundaries of the string, from 0 to `len()`
+    /// included: the valid arguments for `truncate` and `split_at`.
+    pub fn char_boundaries(&self) -> impl Iterator<Item = usize> + '_ {
+        self.char_indices()
+            .map(|(i, _)| i)
+            .chain(iter::once(self.len))
+    }
+
+    /// Return the byte at `index` of the string, or `None` if `index >= len()`, without exposing
+    /// the unused part of the buffer.
+    pub fn byte_at(&self, index: usize) -> Option<u8> {
+        self.as_bytes().get(index).cloned()
+    }
+
+    /// Replace each ASCII byte `b` of the string with `table[b]`, in place, leaving non-ASCII
+    /// bytes unchanged.
+    ///
+    /// # Panics
+    /// Panics if `table` contains a non-ASCII byte, since it could make the string malformed.
+    pub fn translate_ascii(&mut self, table: &[u8; 128]) {
+        assert!(table.is_ascii(), "translation table must only map to ASCII");
+        self.map_ascii_bytes(|b| table[b as usize]);
+    }
+
+    /// Return the string as exactly `N` bytes, e.g. for a fixed-width protocol field, padded with
+    /// `pad` if it is shorter.
+    ///
+    /// A longer string is cut at the last code point boundary at or before `N`, so no code point
+    /// is split. The cut part is then padded too.
+    pub fn to_fixed_bytes<const N: usize>(&self, pad: u8) -> [u8; N] {
+        let mut end = cmp::min(self.len, N);
+        while !self.is_char_boundary(end) {
+            end -= 1;
+        }
+        let mut fixed = [pad; N];
+        fixed[..end].copy_from_slice(&self.as_bytes()[..end]);
+        fixed
+    }
+
+    /// Return an iterator over the code points of the string and their byte offsets, from the
+    /// end. Offsets are of the start of each code point, as with `char_indices`.
+    pub fn char_indices_rev(&self) -> impl Iterator<Item = (usize, char)> + '_ {
+        self.char_indices().rev()
+    }
+}
+
+/// A `fmt::Write` adapter that appends to a `StringWrapper` and, once capacity runs out, keeps
+/// counting the bytes it is given so that the resulting error can report the total length needed.
+///
+/// On overflow, `finish` rolls the string back to its length when the adapter was created.
+struct Appender<'a, T: Buffer> {
+    sw: &'a mut StringWrapper<T>,
+    start_len: usize,
+    needed: usize,
+    overflowed: bool,
+}
+
+impl<'a, T: Buffer> Appender<'a, T> {
+    fn new(sw: &'a mut StringWrapper<T>) -> Self {
+        let start_len = sw.len;
+        Appender {
+            sw,
+            start_len,
+            needed: 0,
+            overflowed: false,
+        }
+    }
+
+    fn push_str(&mut self, s: &str) {
+        if !self.overflowed && self.sw.push_str(s).is_err() {
+            self.overflowed = true;
+        }
+        self.needed = self.needed.saturating_add(s.len());
+    }
+
+    fn finish(self) -> Result<(), Error> {
+        if self.overflowed {
+            self.sw.len = self.start_len;
+            Err(Error::InsufficientLength {
+                expected: self.start_len.saturating_add(self.needed),
+                actual: self.sw.capacity(),
+            })
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl<'a, T: Buffer> fmt::Write for Appender<'a, T> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.push_str(s);
+        Ok(())
+    }
+}
+
+impl<T: OwnedBuffer> FromStr for StringWrapper<T> {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let buffer = T::new();
+        let mut sw = StringWrapper::new(buffer);
+        let res = sw.push_str(s);
+        res.map(|_: ()| sw)
+    }
+}
+
+impl<'a, T: OwnedBuffer> TryFrom<&'a [u8]> for StringWrapper<T> {
+    type Error = Error;
+
+    fn try_from(bytes: &'a [u8]) -> Result<Self, Self::Error> {
+        let s = str::from_utf8(bytes).map_err(Error::InvalidUtf8)?;
+        StringWrapper::copy_from_str(T::new(), s)
+    }
+}
+
+impl<T: OwnedBuffer> StringWrapper<T> {
+    /// Safely construct a new `StringWrapper` from a &str. Unlike `from_str`, this method doesn't
+    /// panic when the &str is too big to fit into the buffer.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use string_wrapper::StringWrapper;
+    ///
+    /// let sw: Option<StringWrapper<[u8; 3]>> = StringWrapper::from_str_safe("foo");
+    /// assert_eq!(format!("{}", sw.unwrap()), "foo");
+    /// ```
+    ///
+    /// ```
+    /// use string_wrapper::StringWrapper;
+    ///
+    /// let sw: Option<StringWrapper<[u8; 3]>> = StringWrapper::from_str_safe("foobar");
+    /// assert_eq!(sw, None);
+    /// ```
+    #[must_use]
+    pub fn from_str_safe(s: &str) -> Option<StringWrapper<T>> {
+        let buffer = T::new();
+        let mut sw = StringWrapper::new(buffer);
+        match sw.push_partial_str(s) {
+            Ok(_) => Some(sw),
+            Err(_) => None,
+        }
+    }
+
+    /// Decode UTF-16 data, e.g. from an FFI boundary, into a new string.
+    ///
+    /// # Errors
+    /// Return `Error::InvalidUtf16` on an unpaired surrogate, or `Error::InsufficientLength` if
+    /// the decoded string doesn't fit in a `T`.
+    pub fn from_utf16(units: &[u16]) -> Result<StringWrapper<T>, Error> {
+        let mut sw = StringWrapper::new(T::new());
+        for c in char::decode_utf16(units.iter().cloned()) {
+            sw.push(c.map_err(Error::InvalidUtf16)?)?;
+        }
+        Ok(sw)
+    }
+
+    /// Collect code points into a new string, like `collect()` but reporting overflow.
+    ///
+    /// # Errors
+    /// Return `Error::InsufficientLength` at the first code point that doesn't fit in a `T`.
+    pub fn try_from_chars<I: IntoIterator<Item = char>>(
+        iter: I,
+    ) -> Result<StringWrapper<T>, Error> {
+        let mut sw = StringWrapper::new(T::new());
+        for c in iter {
+            sw.push(c)?;
+        }
+        Ok(sw)
+    }
+
+    /// Return a new string holding `a` followed by `b`.
+    ///
+    /// # Errors
+    /// Return `Error::InsufficientLength` if the two don't fit together in a `T`.
+    pub fn concat_into<A: Buffer, B: Buffer>(
+        a: &StringWrapper<A>,
+        b: &StringWrapper<B>,
+    ) -> Result<StringWrapper<T>, Error> {
+        let mut sw = StringWrapper::new(T::new());
+        sw.ensure_capacity(a.len().saturating_add(b.len()))?;
+        sw.push_str(a).unwrap();
+        sw.push_str(b).unwrap();
+        Ok(sw)
+    }
+
+    /// Read a string written by `to_wire`: a big-endian `u16` length followed by that many bytes.
+    ///
+    /// # Errors
+    /// Return an `InvalidData` error if the length exceeds the capacity of a `T` or if the bytes
+    /// are not well-formed UTF-8, and forward errors from `r`.
+    pub fn from_wire<R: io::Read>(r: &mut R) -> io::Result<StringWrapper<T>> {
+        let mut len = [0_u8; 2];
+        r.read_exact(&mut len)?;
+        let len = usize::from(u16::from_be_bytes(len));
+
+        let mut buffer = T::new();
+        if len > buffer.as_ref().len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "string too long for the buffer",
+            ));
+        }
+        r.read_exact(&mut buffer.as_mut()[..len])?;
+        if let Err(e) = str::from_utf8(&buffer.as_ref()[..len]) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, e));
+        }
+        Ok(StringWrapper { len, buffer })
+    }
+}
+
+impl<T: Buffer + Clone> StringWrapper<T> {
+    /// Return a copy of the string with ASCII letters converted to upper case.
+    ///
+    /// Non-ASCII bytes are unchanged, so the copy always has the same length.
+    pub fn to_ascii_uppercase(&self) -> Self {
+        let mut copy = self.clone();
+        copy.make_ascii_uppercase();
+        copy
+    }
+
+    /// Return a copy of the string with ASCII letters converted to lower case.
+    ///
+    /// Non-ASCII bytes are unchanged, so the copy always has the same length.
+    pub fn to_ascii_lowercase(&self) -> Self {
+        let mut copy = self.clone();
+        copy.make_ascii_lowercase();
+        copy
+    }
+}
+
+impl<const N: usize> StringWrapper<[u8; N]> {
+    /// The size in bytes of an array-backed string: the `N` bytes of storage plus the length,
+    /// rounded up for alignment.
+    pub const STACK_SIZE: usize = mem::size_of::<Self>();
+
+    /// Create an empty string from an array, in `const` contexts such as statics.
+    pub const fn new_const(buffer: [u8; N]) -> Self {
+        StringWrapper { len: 0, buffer }
+    }
+
+    /// Return the maximum number of bytes an array-backed string can hold, in `const` contexts.
+    ///
+    /// `capacity()` can't be a `const fn` since it goes through `Buffer::as_ref`, which isn't
+    /// `const`.
+    pub const fn capacity_const() -> usize {
+        N
+    }
+
+    /// Create a string holding a copy of `s`, in `const` contexts such as statics. See also the
+    /// `stack_str!` macro.
+    ///
+    /// # Panics
+    /// Panics if `s` is longer than `N` bytes, which fails the build in a `const` context.
+    pub const fn from_str_const(s: &str) -> Self {
+        let bytes = s.as_bytes();
+        assert!(bytes.len() <= N, "string doesn't fit in the buffer");
+        let mut buffer = [0_u8; N];
+        let mut i = 0;
+        // `copy_from_slice` isn't `const`, so copy byte by byte.
+        while i < bytes.len() {
+            buffer[i] = bytes[i];
+            i += 1;
+        }
+        StringWrapper {
+            len: bytes.len(),
+            buffer,
+        }
+    }
+
+    /// Return whether `STACK_SIZE` is at most `limit`, to check a size budget at compile time.
+    ///
+    /// ```
+    /// use string_wrapper::StringWrapper;
+    ///
+    /// const _: () = assert!(StringWrapper::<[u8; 56]>::fits_on_stack(64));
+    /// ```
+    ///
+    /// ```compile_fail
+    /// use string_wrapper::StringWrapper;
+    ///
+    /// const _: () = assert!(StringWrapper::<[u8; 64]>::fits_on_stack(64));
+    /// ```
+    pub const fn fits_on_stack(limit: usize) -> bool {
+        Self::STACK_SIZE <= limit
+    }
+
+    /// Panic if `N` is 0. Called in a `const` item, this fails the build instead.
+    ///
+    /// ```compile_fail
+    /// use string_wrapper::StringWrapper;
+    ///
+    /// const _: () = StringWrapper::<[u8; 0]>::assert_nonzero_capacity();
+    /// ```
+    pub const fn assert_nonzero_capacity() {
+        assert!(N > 0, "StringWrapper buffer has zero capacity");
+    }
+}
+
+impl From<char> for StringWrapper<[u8; 4]> {
+    fn from(c: char) -> Self {
+        let mut buffer = [0_u8; 4];
+        let len = c.encode_utf8(&mut buffer).len();
+        StringWrapper { len, buffer }
+    }
+}
+
+impl StringWrapper<Vec<u8>> {
+    /// Create an empty heap-allocated string that can hold `cap` bytes.
+    ///
+    /// The vector is filled with `cap` zeros, since the capacity of a `StringWrapper` is the
+    /// length of its buffer: `StringWrapper::new(Vec::with_capacity(cap))` can't hold anything.
+    pub fn with_vec_capacity(cap: usize) -> Self {
+        StringWrapper::new(vec![0; cap])
+    }
+}
+
+#[cfg(feature = "use_heapless")]
+impl<const N: usize> StringWrapper<heapless::Vec<u8, N>> {
+    /// Create an empty string from an existing `heapless::Vec`, first filling it to its full
+    /// length with zeros so that the string can use all of its `N` bytes.
+    pub fn from_heapless(mut buffer: heapless::Vec<u8, N>) -> Self {
+        buffer.resize(N, 0).unwrap();
+        StringWrapper::new(buffer)
+    }
+}
+
+/// Fill `dst` with copies of `c`. `dst.len()` must be a multiple of `c.len_utf8()`.
+fn fill_char(dst: &mut [u8], c: char) {
+    if c.is_ascii() {
+        dst.fill(c as u8);
+    } else {
+        for chunk in dst.chunks_mut(c.len_utf8()) {
+            c.encode_utf8(chunk);
+        }
+    }
+}
+
+/// Convert `range` to `start..end` offsets into a string of length `len`, or `None` if it would
+/// overflow.
+fn range_bounds<R: ops::RangeBounds<usize>>(range: R, len: usize) -> Option<(usize, usize)> {
+    let start = match range.start_bound() {
+        ops::Bound::Included(&start) => start,
+        ops::Bound::Excluded(&start) => start.checked_add(1)?,
+        ops::Bound::Unbounded => 0,
+    };
+    let end = match range.end_bound() {
+        ops::Bound::Included(&end) => end.checked_add(1)?,
+        ops::Bound::Excluded(&end) => end,
+        ops::Bound::Unbounded => len,
+    };
+    Some((start, end))
+}
+
+fn starts_well_formed_utf8_sequence(byte: u8) -> bool {
+    // ASCII byte or "leading" byte
+    !(128..192).contains(&byte)
+}
+
+/// Return the length of the UTF-8 sequence started by the leading byte `byte`.
+fn utf8_char_width(byte: u8) -> usize {
+    match byte {
+        0x00..=0x7F => 1,
+        0xC0..=0xDF => 2,
+        0xE0..=0xEF => 3,
+        _ => 4,
+    }
+}
+
+/// A `fmt::Write` adapter inserting into a `StringWrapper` at a given position rather than
+/// appending, e.g. to fill in a placeholder. Returned by `StringWrapper::writer_at`.
+///
+/// Each write is inserted after the previous one, so the output stays in order.
+pub struct Writer<'a, T: Buffer> {
+    sw: &'a mut StringWrapper<T>,
+    start: usize,
+}
+
+impl<'a, T: Buffer> Writer<'a, T> {
+    /// Return the byte offset where the next write will be inserted.
+    pub fn position(&self) -> usize {
+        self.start
+    }
+}
+
+impl<'a, T: Buffer> fmt::Write for Writer<'a, T> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.sw.insert_str(self.start, s).map_err(|_| fmt::Error)?;
+        self.start += s.len();
+        Ok(())
+    }
+}
+
+/// Decodes UTF-8 into a `StringWrapper` in chunks, e.g. from socket reads, where a code point may
+/// be split across chunks.
+///
+/// Up to 3 bytes of an incomplete code point at the end of a chunk are held back until the next
+/// chunk completes it.
+pub struct StreamPusher<T: Buffer> {
+    sw: StringWrapper<T>,
+    pending: [u8; 4],
+    pending_len: usize,
+}
+
+impl<T: Buffer> StreamPusher<T> {
+    /// Create a pusher appending to `sw`.
+    pub fn new(sw: StringWrapper<T>) -> Self {
+        StreamPusher {
+            sw,
+            pending: [0; 4],
+            pending_len: 0,
+        }
+    }
+
+    /// View the string decoded so far, without the held back bytes.
+    pub fn get_ref(&self) -> &StringWrapper<T> {
+        &self.sw
+    }
+
+    /// Append the complete code points in `bytes`, along with the bytes held back from the
+    /// previous chunk, and hold back a trailing incomplete code point if any.
+    ///
+    /// # Errors
+    /// Return `Error::InvalidUtf8` or `Error::InsufficientLength`, with the string and the held
+    /// back bytes unchanged.
+    pub fn push_incremental(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        let mut head = self.pending;
+        let mut head_len = 0;
+        let mut rest = bytes;
+        if self.pending_len > 0 {
+            let width = utf8_char_width(head[0]);
+            let take = cmp::min(width - self.pending_len, rest.len());
+            head[self.pending_len..self.pending_len + take].copy_from_slice(&rest[..take]);
+            rest = &rest[take..];
+            let filled = self.pending_len + take;
+            match str::from_utf8(&head[..filled]) {
+                Ok(_) => head_len = filled,
+                // Still incomplete, so `bytes` is used up.
+                Err(ref e) if e.error_len().is_none() => {
+                    self.pending = head;
+                    self.pending_len = filled;
+                    return Ok(());
+                }
+                Err(e) => return Err(Error::InvalidUtf8(e)),
+            }
+        }
+
+        let (body, tail) = match str::from_utf8(rest) {
+            Ok(body) => (body, &[][..]),
+            Err(ref e) if e.error_len().is_none() => {
+                let (valid, tail) = rest.split_at(e.valid_up_to());
+                // `from_utf8` checked everything up to `valid_up_to`.
+                (unsafe { str::from_utf8_unchecked(valid) }, tail)
+            }
+            Err(e) => return Err(Error::InvalidUtf8(e)),
+        };
+        self.sw.ensure_capacity(head_len + body.len())?;
+        self.sw
+            .push_str(str::from_utf8(&head[..head_len]).unwrap())
+            .unwrap();
+        self.sw.push_str(body).unwrap();
+        self.pending[..tail.len()].copy_from_slice(tail);
+        self.pending_len = tail.len();
+        Ok(())
+    }
+
+    /// Return the decoded string.
+    ///
+    /// # Errors
+    /// Return `Error::InvalidUtf8` if an incomplete code point is still held back.
+    pub fn finish(self) -> Result<StringWrapper<T>, Error> {
+        match str::from_utf8(&self.pending[..self.pending_len]) {
+            Ok(_) => Ok(self.sw),
+            Err(e) => Err(Error::InvalidUtf8(e)),
+        }
+    }
+}
+
+/// A read-only string borrowing an existing byte slice, without copying it.
+///
+/// It offers the non-mutating part of the `StringWrapper` API (`len`, `Deref<Target = str>`,
+/// `Display`, comparisons), so borrowed and owned strings can be handled alike.
+///
+/// ```
+/// use string_wrapper::ReadOnlyStringWrapper;
+///
+/// let s = ReadOnlyStringWrapper::new(b"radix").unwrap();
+/// assert_eq!(s.len(), 5);
+/// assert_eq!(s.to_uppercase(), "RADIX");
+/// ```
+#[derive(Clone, Copy, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct ReadOnlyStringWrapper<'a> {
+    s: &'a str,
+}
+
+impl<'a> ReadOnlyStringWrapper<'a> {
+    /// Wrap a byte slice.
+    ///
+    /// # Errors
+    /// Return `Error::InvalidUtf8` if `bytes` is not well-formed UTF-8.
+    pub fn new(bytes: &'a [u8]) -> Result<Self, Error> {
+        match str::from_utf8(bytes) {
+            Ok(s) => Ok(ReadOnlyStringWrapper { s }),
+            Err(e) => Err(Error::InvalidUtf8(e)),
+        }
+    }
+
+    /// Return the length of the string in bytes.
+    pub fn len(&self) -> usize {
+        self.s.len()
+    }
+
+    /// Return `true` if the string is empty.
+    pub fn is_empty(&self) -> bool {
+        self.s.is_empty()
+    }
+
+    /// Return the string, borrowed for as long as the underlying slice.
+    pub fn as_str(&self) -> &'a str {
+        self.s
+    }
+}
+
+impl<'a> From<&'a str> for ReadOnlyStringWrapper<'a> {
+    fn from(s: &'a str) -> Self {
+        ReadOnlyStringWrapper { s }
+    }
+}
+
+impl<'a> ops::Deref for ReadOnlyStringWrapper<'a> {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.s
+    }
+}
+
+impl<'a> fmt::Display for ReadOnlyStringWrapper<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self.s, f)
+    }
+}
+
+impl<'a> fmt::Debug for ReadOnlyStringWrapper<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.s, f)
+    }
+}
+
+// Only in this direction: `StringWrapper<T>: PartialEq<ReadOnlyStringWrapper>` would stop the
+// compiler from inferring the other side of `owned == ...`. Use `owned.as_read_only()` instead.
+impl<'a, T: Buffer> PartialEq<StringWrapper<T>> for ReadOnlyStringWrapper<'a> {
+    fn eq(&self, other: &StringWrapper<T>) -> bool {
+        self.s == &**other
+    }
+}
+
+impl<'a, T: Buffer> PartialOrd<StringWrapper<T>> for ReadOnlyStringWrapper<'a> {
+    fn partial_cmp(&self, other: &StringWrapper<T>) -> Option<cmp::Ordering> {
+        Some(self.s.cmp(&**other))
+    }
+}
+
+impl<T> ops::Deref for StringWrapper<T>
+where
+    T: Buffer,
+{
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        unsafe { str::from_utf8_unchecked(&self.buffer.as_ref()[..self.len]) }
+    }
+}
+
+impl<T> ops::DerefMut for StringWrapper<T>
+where
+    T: Buffer,
+{
+    fn deref_mut(&mut self) -> &mut str {
+        unsafe { str::from_utf8_unchecked_mut(&mut self.buffer.as_mut()[..self.len]) }
+    }
+}
+
+impl<T> core::borrow::Borrow<str> for StringWrapper<T>
+where
+    T: Buffer,
+{
+    fn borrow(&self) -> &str {
+        self
+    }
+}
+
+/// `StringWrapper` hashes like `str`, and `str` and `[u8]` hash differently, so this impl can't be
+/// used to look up a `StringWrapper` by bytes in a hashed collection such as `HashSet`; use
+/// `ByteKey` for that. It is consistent with `Eq` and `Ord`, so lookups in ordered collections
+/// such as `BTreeSet` work.
+impl<T> core::borrow::Borrow<[u8]> for StringWrapper<T>
+where
+    T: Buffer,
+{
+    fn borrow(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+/// Wrapper making a `StringWrapper` hash like `[u8]` rather than `str`, so that it can be looked
+/// up by bytes in hashed collections.
+///
+/// ```
+/// use std::collections::HashSet;
+/// use std::str::FromStr;
+/// use string_wrapper::{ByteKey, StringWrapper};
+///
+/// let mut set = HashSet::new();
+/// set.insert(ByteKey(StringWrapper::<[u8; 8]>::from_str("foo").unwrap()));
+/// assert!(set.contains(&b"foo"[..]));
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct ByteKey<W>(pub W);
+
+impl<T: Buffer> PartialEq for ByteKey<StringWrapper<T>> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_bytes() == other.0.as_bytes()
+    }
+}
+
+impl<T: Buffer> Eq for ByteKey<StringWrapper<T>> {}
+
+impl<T: Buffer> hash::Hash for ByteKey<StringWrapper<T>> {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        self.0.as_bytes().hash(state);
+    }
+}
+
+impl<T: Buffer> core::borrow::Borrow<[u8]> for ByteKey<StringWrapper<T>> {
+    fn borrow(&self) -> &[u8] {
+        self.0.as_bytes()
+    }
+}
+
+/// Comparison adapter skipping the given bytes, e.g. separators, so that `"a-b-c"` and `"abc"`
+/// compare equal when ignoring `b'-'`.
+///
+/// Each side skips its own set of bytes. The remaining bytes compare like `str`. Ignoring
+/// non-ASCII bytes can split code points, but comparisons still work byte by byte.
+///
+/// ```
+/// use std::str::FromStr;
+/// use string_wrapper::{IgnoreBytes, StringWrapper};
+///
+/// let a: StringWrapper<[u8; 8]> = StringWrapper::from_str("a-b-c").unwrap();
+/// let b: StringWrapper<[u8; 8]> = StringWrapper::from_str("abc").unwrap();
+/// assert_eq!(IgnoreBytes(&a, b"-"), IgnoreBytes(&b, b"-"));
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct IgnoreBytes<'a, T: Buffer>(pub &'a StringWrapper<T>, pub &'a [u8]);
+
+impl<'a, T: Buffer> IgnoreBytes<'a, T> {
+    fn kept_bytes(&self) -> impl Iterator<Item = u8> + 'a {
+        let ignored = self.1;
+        self.0
+            .as_bytes()
+            .iter()
+            .cloned()
+            .filter(move |b| !ignored.contains(b))
+    }
+}
+
+impl<'a, T: Buffer> PartialEq for IgnoreBytes<'a, T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.kept_bytes().eq(other.kept_bytes())
+    }
+}
+
+impl<'a, T: Buffer> Eq for IgnoreBytes<'a, T> {}
+
+impl<'a, T: Buffer> PartialOrd for IgnoreBytes<'a, T> {
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<'a, T: Buffer> Ord for IgnoreBytes<'a, T> {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        self.kept_bytes().cmp(other.kept_bytes())
+    }
+}
+
+impl<T> AsRef<str> for StringWrapper<T>
+where
+    T: Buffer,
+{
+    fn as_ref(&self) -> &str {
+        self
+    }
+}
+
+impl<T> AsRef<[u8]> for StringWrapper<T>
+where
+    T: Buffer,
+{
+    fn as_ref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl<T> fmt::Display for StringWrapper<T>
+where
+    T: Buffer,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
+
+/// Return a value that displays `items` separated by `sep`, without building an intermediate
+/// string, e.g. for logging.
+///
+/// ```
+/// use std::str::FromStr;
+/// use string_wrapper::{display_joined, StringWrapper};
+///
+/// let items: Vec<StringWrapper<[u8; 4]>> =
+///     vec![StringWrapper::from_str("a").unwrap(), StringWrapper::from_str("b").unwrap()];
+/// assert_eq!(display_joined(&items, "+").to_string(), "a+b");
+/// ```
+pub fn display_joined<'a, T: Buffer>(
+    items: &'a [StringWrapper<T>],
+    sep: &'a str,
+) -> impl fmt::Display + 'a {
+    DisplayJoined { items, sep }
+}
+
+struct DisplayJoined<'a, T: Buffer> {
+    items: &'a [StringWrapper<T>],
+    sep: &'a str,
+}
+
+impl<'a, T: Buffer> fmt::Display for DisplayJoined<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, item) in self.items.iter().enumerate() {
+            if i > 0 {
+                f.write_str(self.sep)?;
+            }
+            f.write_str(item)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T> fmt::Debug for StringWrapper<T>
+where
+    T: Buffer,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: Buffer> PartialEq for StringWrapper<T> {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+// We need to explicitly define Eq here, because the derive logic only impls it when T is also Eq.
+impl<T: Buffer> Eq for StringWrapper<T> {}
+
+impl<T: Buffer> PartialOrd for StringWrapper<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// Hashing follows `str`, so `Borrow<[u8]>` is only good for ordered collections; see its docs.
+#[allow(clippy::impl_hash_borrow_with_str_and_bytes)]
+impl<T: Buffer> hash::Hash for StringWrapper<T> {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        (**self).hash(state);
+    }
+}
+
+impl<T: Buffer> Ord for StringWrapper<T> {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        (**self).cmp(&**other)
+    }
+}
+
+impl<T: Buffer> fmt::Write for StringWrapper<T> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let res = self.push_str(s);
+        if res.is_err() {
+            Err(fmt::Error)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn write_char(&mut self, c: char) -> fmt::Result {
+        self.push(c).map_err(|_| fmt::Error)
+    }
+}
+
+/// `Extend` can't report overflow, so this stops at capacity: once a string doesn't fit, as much
+/// of it as fits is appended, cut on a code point boundary, and the rest of the iterator is not
+/// consumed. Use `push_str` in a loop to handle overflow.
+impl<'a, T: Buffer, U: Buffer> Extend<&'a StringWrapper<U>> for StringWrapper<T> {
+    fn extend<I: IntoIterator<Item = &'a StringWrapper<U>>>(&mut self, iter: I) {
+        for sw in iter {
+            if self.push_partial_str(sw).is_err() {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(feature = "use_serde")]
+impl<T: Buffer> serde::Serialize for StringWrapper<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.to_string().serialize(serializer)
+    }
+}
+
+#[cfg(feature = "use_serde")]
+impl<'de, T: OwnedBuffer> serde::Deserialize<'de> for StringWrapper<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer
+            .deserialize_str(StringWrapperVisitor(std::marker::PhantomData))?
+            .map_err(too_long::<T, D::Error>)
+    }
+}
+
+// Deserializing through a visitor lets us copy borrowed strings straight into the buffer, instead
+// of going through an intermediate `String`.
+//
+// A string that doesn't fit is reported as `Err(len)` rather than as an error from the visitor,
+// so that the error is built outside the deserializer like before, and e.g. serde_json doesn't
+// add a position to its message.
+#[cfg(feature = "use_serde")]
+struct StringWrapperVisitor<T>(std::marker::PhantomData<T>);
+
+#[cfg(feature = "use_serde")]
+fn too_long<T: OwnedBuffer, E: serde::de::Error>(len: usize) -> E {
+    let buff = T::new();
+    let msg: String = format!("string that can fit into {} bytes", buff.as_ref().len());
+
+    E::invalid_length(len, &StringExpected(msg))
+}
+
+#[cfg(feature = "use_serde")]
+impl<'de, T: OwnedBuffer> serde::de::Visitor<'de> for StringWrapperVisitor<T> {
+    type Value = Result<StringWrapper<T>, usize>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a UTF-8 string")
+    }
+
+    fn visit_str<E: serde::de::Error>(self, s: &str) -> Result<Self::Value, E> {
+        Ok(StringWrapper::from_str_safe(s).ok_or(s.len()))
+    }
+
+    fn visit_bytes<E: serde::de::Error>(self, bytes: &[u8]) -> Result<Self::Value, E> {
+        match str::from_utf8(bytes) {
+            Ok(s) => self.visit_str(s),
+            Err(_) => Err(E::invalid_value(serde::de::Unexpected::Bytes(bytes), &self)),
+        }
+    }
+}
+
+/// Wrapper to (de)serialize a `StringWrapper` as raw UTF-8 bytes rather than as a string.
+///
+/// This suits binary formats where fixed-size strings are best encoded as length-prefixed bytes.
+/// Deserializing checks that the bytes are well-formed UTF-8 and fit in the buffer.
+#[cfg(feature = "use_serde")]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct AsBytes<W>(pub W);
+
+#[cfg(feature = "use_serde")]
+impl<T: Buffer> serde::Serialize for AsBytes<StringWrapper<T>> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(self.0.as_bytes())
+    }
+}
+
+#[cfg(feature = "use_serde")]
+impl<'de, T: OwnedBuffer> serde::Deserialize<'de> for AsBytes<StringWrapper<T>> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer
+            .deserialize_bytes(StringWrapperVisitor(std::marker::PhantomData))?
+            .map(AsBytes)
+            .map_err(too_long::<T, D::Error>)
+    }
+}
+
+// It seems silly that I can't just pass a String to invalid_length, but there's no implementation
+// of Expected for String, so...
+#[cfg(feature = "use_serde")]
+struct StringExpected(String);
+#[cfg(feature = "use_serde")]
+impl serde::de::Expected for StringExpected {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.0, formatter)
+    }
+}
+
+#[allow(clippy::needless_lifetimes)]
+unsafe impl<'a, T: ?Sized + Buffer> Buffer for &'a mut T {
+    fn as_ref(&self) -> &[u8] {
+        (**self).as_ref()
+    }
+    fn as_mut(&mut self) -> &mut [u8] {
+        (**self).as_mut()
+    }
+}
+
+unsafe impl<T: ?Sized + Buffer> Buffer for Box<T> {
+    fn as_ref(&self) -> &[u8] {
+        (**self).as_ref()
+    }
+    fn as_mut(&mut self) -> &mut [u8] {
+        (**self).as_mut()
+    }
+}
+
+unsafe impl Buffer for Vec<u8> {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+    fn as_mut(&mut self) -> &mut [u8] {
+        self
+    }
+}
+
+/// Like for `Vec<u8>`, the string can only use the *length* of a `heapless::Vec`, not its
+/// capacity: a `heapless::Vec<u8, N>` should be filled to its full length before being used as a
+/// buffer, see `StringWrapper::from_heapless`.
+#[cfg(feature = "use_heapless")]
+unsafe impl<const N: usize> Buffer for heapless::Vec<u8, N> {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+    fn as_mut(&mut self) -> &mut [u8] {
+        self
+    }
+}
+
+#[cfg(feature = "use_heapless")]
+impl<const N: usize> OwnedBuffer for heapless::Vec<u8, N> {
+    fn new() -> Self {
+        let mut buffer = heapless::Vec::new();
+        buffer.resize(N, 0).unwrap();
+        buffer
+    }
+}
+
+unsafe impl Buffer for [u8] {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+    fn as_mut(&mut self) -> &mut [u8] {
+        self
+    }
+}
+
+unsafe impl<const N: usize> Buffer for [u8; N] {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+
+    fn as_mut(&mut self) -> &mut [u8] {
+        self
+    }
+}
+
+impl<const N: usize> OwnedBuffer for [u8; N] {
+    fn new() -> Self { [0_u8; N] }
+}
+
+/// A newtype around `[u8; N]`, showing how to implement `Buffer` and `OwnedBuffer` for your own
+/// fixed-size buffer types.
+#[derive(Clone, Copy)]
+pub struct ArrayBuffer<const N: usize>(pub [u8; N]);
+
+unsafe impl<const N: usize> Buffer for ArrayBuffer<N> {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+
+    fn as_mut(&mut self) -> &mut [u8] {
+        &mut self.0
+    }
+}
+
+impl<const N: usize> OwnedBuffer for ArrayBuffer<N> {
+    fn new() -> Self {
+        ArrayBuffer([0_u8; N])
+    }
+}
+
+macro_rules! aligned_buffers {
+    ($($name:ident: $align:literal),*) => {
+        $(
+            #[doc = concat!(
+                "A buffer aligned to ", stringify!($align), " bytes, e.g. for DMA, forwarding ",
+                "`Buffer` and `OwnedBuffer` to the wrapped buffer."
+            )]
+            #[derive(Clone, Copy)]
+            #[repr(align($align))]
+            pub struct $name<B>(pub B);
+
+            unsafe impl<B: Buffer> Buffer for $name<B> {
+                fn as_ref(&self) -> &[u8] {
+                    self.0.as_ref()
+                }
+
+                fn as_mut(&mut self) -> &mut [u8] {
+                    self.0.as_mut()
+                }
+            }
+
+            impl<B: OwnedBuffer> OwnedBuffer for $name<B> {
+                fn new() -> Self {
+                    $name(B::new())
+                }
+            }
+        )*
+    };
+}
+
+aligned_buffers!(Aligned4: 4, Aligned8: 8, Aligned16: 16, Aligned32: 32, Aligned64: 64);
+
+/// Like a `format!` but uses a fixed size buffer of specified length
+///
+/// `stack_format!(N, ...)` returns `Result<StringWrapper<[u8; N]>, fmt::Error>`.
+/// `stack_format!(try N, ...)` returns `Result<StringWrapper<[u8; N]>, Error>` instead, with
+/// `Error::InsufficientLength` telling how long the output would have been.
+///
+/// ```
+/// #[macro_use]
+/// extern crate string_wrapper;
+/// use string_wrapper::Error;
+///
+/// # fn main() {
+/// assert_eq!(&*stack_format!(try 8, "{}-{}", 4, 2).unwrap(), "4-2");
+/// assert_eq!(
+///     stack_format!(try 4, "{}", 12345),
+///     Err(Error::InsufficientLength { expected: 5, actual: 4 })
+/// );
+/// # }
+/// ```
+#[macro_export]
+macro_rules! stack_format {
+    (try $limit:literal, $($args:tt)*) => {
+        {
+            let mut sw = $crate::StringWrapper::new([0u8; $limit]);
+            let r = sw.try_format(format_args!($($args)*));
+            r.map(|_: ()| sw)
+        }
+    };
+    ($limit:literal, $($args:tt)*) => {
+        {
+            use std::fmt::Write;
+            let mut sw = $crate::StringWrapper::new([0u8; $limit]);
+            let r = write!(sw, $($args)*);
+            r.map(|_: ()| sw)
+        }
+    };
+}
+
+/// Fail the build unless the array-backed string type `$t` can hold at least `$min` bytes.
+///
+/// ```
+/// #[macro_use]
+/// extern crate string_wrapper;
+/// use string_wrapper::StringWrapper;
+///
+/// type Name = StringWrapper<[u8; 16]>;
+/// ensure_capacity_type!(Name, 16);
+/// # fn main() {}
+/// ```
+///
+/// ```compile_fail
+/// #[macro_use]
+/// extern crate string_wrapper;
+/// use string_wrapper::StringWrapper;
+///
+/// ensure_capacity_type!(StringWrapper<[u8; 8]>, 16);
+/// # fn main() {}
+/// ```
+#[macro_export]
+macro_rules! ensure_capacity_type {
+    ($t:ty, $min:expr) => {
+        const _: () = assert!(
+            <$t>::capacity_const() >= $min,
+            concat!(
+                "capacity of `",
+                stringify!($t),
+                "` is less than ",
+                stringify!($min)
+            )
+        );
+    };
+}
+
+/// Create a `StringWrapper<[u8; N]>` holding a string constant, where `N` is exactly its length
+/// in bytes. It can be used in `const` and `static` items.
+///
+/// ```
+/// #[macro_use]
+/// extern crate string_wrapper;
+/// use string_wrapper::StringWrapper;
+///
+/// static GREETING: StringWrapper<[u8; 6]> = stack_str!("hi ☃");
+///
+/// # fn main() {
+/// assert_eq!(&*GREETING, "hi ☃");
+/// assert_eq!(GREETING.extra_capacity(), 0);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! stack_str {
+    ($s:expr) => {{
+        const S: &str = $s;
+        $crate::StringWrapper::<[u8; S.len()]>::from_str_const(S)
+    }};
+}
+
+/// Like `write!`, but appends to an existing `StringWrapper` with `try_format`, so that overflow
+/// is reported as an `Error`.
+#[macro_export]
+macro_rules! append_fmt {
+    ($sw:expr, $($args:tt)*) => {
+        $sw.try_format(format_args!($($args)*))
+    };
+}
+
+#[cfg(test)]
+#[allow(clippy::non_ascii_literal)]
+mod tests {
+    use Error;
+    use std;
     use std::cmp;
+    use std::convert::TryFrom;
     use std::hash;
     use std::str::FromStr;
 
-    #[cfg(feature = "use_serde")]
-    extern crate serde_json;
+    #[cfg(feature = "use_serde")]
+    extern crate bincode;
+    #[cfg(feature = "use_heapless")]
+    use heapless;
+    #[cfg(feature = "use_serde")]
+    extern crate serde_json;
+
+    #[cfg(feature = "use_serde")]
+    use AsBytes;
+
+    use display_joined;
+    use Alignment;
+    use ArrayBuffer;
+    use Buffer;
+    use ByteKey;
+    use IgnoreBytes;
+    use OwnedBuffer;
+    use ReadOnlyStringWrapper;
+    use StreamPusher;
+    use StringWrapper;
+    use Writer;
+    use {Aligned16, Aligned32, Aligned4, Aligned64, Aligned8};
+
+    const _: () = assert!(StringWrapper::<[u8; 24]>::fits_on_stack(32));
+    const _: () = StringWrapper::<[u8; 1]>::assert_nonzero_capacity();
+    ensure_capacity_type!(StringWrapper<[u8; 4]>, 4);
+
+    #[test]
+    #[allow(dead_code)]
+    fn traits() {
+        // A simple way to ensure that Eq is implemented for StringWrapper
+        #[derive(Eq, PartialEq, Ord, PartialOrd)]
+        struct Foo {
+            x: StringWrapper<[u8; 64]>,
+        }
+    }
+
+    #[test]
+    #[allow(clippy::eq_op)]
+    fn eq() {
+        let mut s = StringWrapper::<[u8; 3]>::new(*b"000");
+        assert_eq!(s, s);
+        s.push_str("foo").unwrap();
+        let mut s2 = StringWrapper::<[u8; 3]>::new(*b"000");
+        s2.push_str("foo").unwrap();
+        assert_eq!(s, s2);
+
+        let mut s3 = StringWrapper::<[u8; 3]>::new(*b"000");
+        s3.push_str("bar").unwrap();
+        assert_ne!(s, s3);
+    }
+
+    #[test]
+    fn eq_only_to_length() {
+        let a = StringWrapper::<[u8; 5]>::new(*b"aaaaa");
+        let b = StringWrapper::<[u8; 5]>::new(*b"bbbbb");
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn ord() {
+        let mut s = StringWrapper::<[u8; 3]>::new(*b"000");
+        let mut s2 = StringWrapper::<[u8; 3]>::new(*b"000");
+        s.push_str("a").unwrap();
+        s2.push_str("b").unwrap();
+        assert_eq!(s.partial_cmp(&s2), Some(cmp::Ordering::Less));
+        assert_eq!(s.cmp(&s2), cmp::Ordering::Less);
+    }
+
+    #[test]
+    fn ord_only_to_length() {
+        let mut s = StringWrapper::<[u8; 3]>::new(*b"000");
+        let mut s2 = StringWrapper::<[u8; 3]>::new(*b"111");
+        assert_eq!(s.partial_cmp(&s2), Some(cmp::Ordering::Equal));
+        assert_eq!(s.cmp(&s2), cmp::Ordering::Equal);
+
+        s.push_str("aa").unwrap();
+        s2.push_str("aa").unwrap();
+        assert_eq!(s.partial_cmp(&s2), Some(cmp::Ordering::Equal));
+        assert_eq!(s.cmp(&s2), cmp::Ordering::Equal);
+    }
+
+    #[cfg(test)]
+    fn hash<T: ?Sized + hash::Hash>(t: &T) -> u64 {
+        // who knows why this isn't in std
+        let mut h = std::collections::hash_map::DefaultHasher::new();
+        t.hash(&mut h);
+        hash::Hasher::finish(&h)
+    }
+
+    #[test]
+    fn hash_only_to_length() {
+        let mut s = StringWrapper::<[u8; 64]>::new([0_u8; 64]);
+        let mut s2 = StringWrapper::<[u8; 64]>::new([1_u8; 64]);
+        assert_eq!(hash(&s), hash(&s2));
+        s.push_str("a").unwrap();
+        assert!(hash(&s) != hash(&s2));
+        s2.push_str("a").unwrap();
+        assert_eq!(hash(&s), hash(&s2));
+    }
+
+    #[test]
+    fn from_str() {
+        let s: StringWrapper<[u8; 64]> = StringWrapper::from_str("OMG!").unwrap();
+        let mut s2 = StringWrapper::new([0_u8; 64]);
+        s2.push_str("OMG!").unwrap();
+        assert_eq!(s, s2);
+    }
+
+    #[test]
+    fn it_works() {
+        let mut s = StringWrapper::new([0; 10]);
+        assert_eq!(&*s, "");
+        assert_eq!(s.len(), 0);
+        assert_eq!(s.capacity(), 10);
+        assert_eq!(s.extra_capacity(), 10);
+
+        assert_eq!(&*s, "");
+        assert_eq!(s.len(), 0);
+        assert_eq!(s.capacity(), 10);
+        assert_eq!(s.extra_capacity(), 10);
+
+        s.push_str("a").unwrap();
+        assert_eq!(&*s, "a");
+        assert_eq!(s.len(), 1);
+        assert_eq!(s.capacity(), 10);
+        assert_eq!(s.extra_capacity(), 9);
+
+        assert_eq!(s.push('é'), Ok(()));
+        assert_eq!(&*s, "aé");
+        assert_eq!(s.len(), 3);
+        assert_eq!(s.capacity(), 10);
+        assert_eq!(s.extra_capacity(), 7);
+
+        assert_eq!(s.push_partial_str("~~~"), Ok(()));
+        assert_eq!(&*s, "aé~~~");
+        assert_eq!(s.len(), 6);
+        assert_eq!(s.capacity(), 10);
+        assert_eq!(s.extra_capacity(), 4);
+
+        assert_eq!(s.push_partial_str("hello"), Err(4));
+        assert_eq!(&*s, "aé~~~hell");
+        assert_eq!(s.len(), 10);
+        assert_eq!(s.capacity(), 10);
+        assert_eq!(s.extra_capacity(), 0);
+
+        s.truncate(6);
+        assert_eq!(&*s, "aé~~~");
+        assert_eq!(s.len(), 6);
+        assert_eq!(s.capacity(), 10);
+        assert_eq!(s.extra_capacity(), 4);
+
+        assert_eq!(s.push_partial_str("_🌠"), Err(1));
+        assert_eq!(&*s, "aé~~~_");
+        assert_eq!(s.len(), 7);
+        assert_eq!(s.capacity(), 10);
+        assert_eq!(s.extra_capacity(), 3);
+
+        assert_eq!(
+            s.push('🌠'),
+            Err(Error::InsufficientLength {
+                expected: 11,
+                actual: 10
+            })
+        );
+        assert_eq!(&*s, "aé~~~_");
+        assert_eq!(s.len(), 7);
+        assert_eq!(s.capacity(), 10);
+        assert_eq!(s.extra_capacity(), 3);
+
+        let buffer: [u8; 10] = s.into_buffer();
+        assert_eq!(&buffer, b"a\xC3\xA9~~~_ell");
+        assert_eq!(format!("{}", s), "aé~~~_");
+        assert_eq!(format!("{:?}", s), r#""aé~~~_""#);
+
+        assert_eq!(s.push_partial_str("ô!?"), Err(3));
+        assert_eq!(&*s, "aé~~~_ô!");
+        assert_eq!(s.len(), 10);
+        assert_eq!(s.capacity(), 10);
+        assert_eq!(s.extra_capacity(), 0);
+    }
+
+    #[cfg(feature = "use_serde")]
+    #[test]
+    fn test_serde() {
+        let mut s = StringWrapper::new([0u8; 64]);
+        s.push_str("foobar").unwrap();
+        let json = serde_json::to_string(&s).unwrap();
+        assert_eq!(json, "\"foobar\"");
+        let s2 = serde_json::from_str(&json).unwrap();
+        assert_eq!(s, s2);
+    }
+
+    #[cfg(feature = "use_serde")]
+    #[test]
+    #[allow(clippy::needless_borrow)]
+    fn deserialize_too_long() {
+        let json = "\"12345\"";
+        match serde_json::from_str::<StringWrapper<[u8; 3]>>(&json) {
+            Err(e) => {
+                assert_eq!(
+                    format!("{}", e),
+                    "invalid length 5, expected string that can fit into 3 bytes"
+                )
+            }
+            Ok(x) => panic!("Expected error, got success: {:?}", x),
+        }
+    }
+
+    #[allow(clippy::clone_on_copy)]
+    #[test]
+    fn clone() {
+        let s = StringWrapper::new([0_u8; 64]);
+        let y: StringWrapper<[u8; 64]> = s.clone();
+        println!("s: {}, y: {}", s, y);
+    }
+
+    #[test]
+    fn push_display_list() {
+        let mut s = StringWrapper::new([0_u8; 16]);
+        s.push_str("n: ").unwrap();
+        s.push_display_list(&[1, 22, 333], ", ").unwrap();
+        assert_eq!(&*s, "n: 1, 22, 333");
+
+        s.push_display_list::<u8>(&[], ", ").unwrap();
+        assert_eq!(&*s, "n: 1, 22, 333");
+    }
+
+    #[test]
+    fn push_display_list_overflow() {
+        let mut s = StringWrapper::new([0_u8; 8]);
+        s.push_str("n: ").unwrap();
+        assert_eq!(
+            s.push_display_list(&[1, 22, 333], ", "),
+            Err(Error::InsufficientLength {
+                expected: 13,
+                actual: 8
+            })
+        );
+        assert_eq!(&*s, "n: ");
+    }
+
+    #[test]
+    fn capacity_const() {
+        const _: () = assert!(StringWrapper::<[u8; 16]>::capacity_const() == 16);
+        assert_eq!(
+            StringWrapper::<[u8; 16]>::capacity_const(),
+            StringWrapper::new([0_u8; 16]).capacity()
+        );
+    }
+
+    #[test]
+    fn case_fold_into() {
+        let s: StringWrapper<[u8; 16]> = StringWrapper::from_str("HeLLo Straße").unwrap();
+        let folded = s.case_fold_into::<16>().unwrap();
+        assert_eq!(&*folded, "hello straße");
+
+        // 'İ' lowercases to "i\u{307}", which is one byte longer.
+        let s: StringWrapper<[u8; 4]> = StringWrapper::from_str("İA").unwrap();
+        assert_eq!(&*s.case_fold_into::<4>().unwrap(), "i\u{307}a");
+        assert_eq!(s.case_fold_into::<3>(), None);
+    }
+
+    #[test]
+    fn contains() {
+        let s: StringWrapper<[u8; 16]> = StringWrapper::from_str("né ☃ 🌠").unwrap();
+        assert!(s.contains_str("é ☃"));
+        assert!(s.contains_str(""));
+        assert!(!s.contains_str("e"));
+        assert!(s.contains_char('☃'));
+        assert!(!s.contains_char('e'));
+    }
+
+    #[test]
+    fn starts_with_ends_with() {
+        let s: StringWrapper<[u8; 16]> = StringWrapper::from_str("né ☃ 🌠").unwrap();
+        assert!(s.starts_with_str("né"));
+        assert!(!s.starts_with_str("ne"));
+        assert!(s.ends_with_str("☃ 🌠"));
+        assert!(!s.ends_with_str("☃"));
+    }
+
+    #[test]
+    fn test_stack_format() {
+        let s = stack_format!(128, "{}{}{}", 1, 2, 3).unwrap();
+        assert_eq!(&*s, "123");
+
+        let s2 = stack_format!(4, "{}", 12345);
+        s2.expect_err("expected error");
+    }
+
+    #[test]
+    fn to_uppercase_into() {
+        let s: StringWrapper<[u8; 8]> = StringWrapper::from_str("straße").unwrap();
+        let upper: StringWrapper<[u8; 8]> = s.to_uppercase_into().unwrap();
+        assert_eq!(&*upper, "STRASSE");
+        assert_eq!(
+            s.to_uppercase_into::<[u8; 6]>(),
+            Err(Error::InsufficientLength {
+                expected: 7,
+                actual: 6
+            })
+        );
+
+        // Case mapping is not locale-specific: dotless ı upper-cases to I, and İ lower-cases to
+        // i followed by a combining dot.
+        let turkish: StringWrapper<[u8; 8]> = StringWrapper::from_str("ıi İ").unwrap();
+        let upper: StringWrapper<[u8; 8]> = turkish.to_uppercase_into().unwrap();
+        assert_eq!(&*upper, "II İ");
+        let lower: StringWrapper<[u8; 8]> = turkish.to_lowercase_into().unwrap();
+        assert_eq!(&*lower, "ıi i\u{307}");
+        assert_eq!(lower.len(), turkish.len() + 1);
+    }
+
+    #[test]
+    fn test_stack_format_try() {
+        let s: StringWrapper<[u8; 8]> = stack_format!(try 8, "{}é{}", 1, 2).unwrap();
+        assert_eq!(&*s, "1é2");
+        let s = stack_format!(try 4, "{}", 1234).unwrap();
+        assert_eq!(s.extra_capacity(), 0);
+
+        assert_eq!(
+            stack_format!(try 4, "{}", 12345),
+            Err(Error::InsufficientLength {
+                expected: 5,
+                actual: 4
+            })
+        );
+        assert_eq!(
+            stack_format!(try 4, "{}-{}", "abc", "defgh"),
+            Err(Error::InsufficientLength {
+                expected: 9,
+                actual: 4
+            })
+        );
+    }
+
+    #[test]
+    fn find() {
+        let s: StringWrapper<[u8; 16]> = StringWrapper::from_str("é-ab-é-ab").unwrap();
+        assert_eq!(s.find_str("ab"), Some(3));
+        assert_eq!(s.rfind_str("ab"), Some(9));
+        assert_eq!(s.find_char('é'), Some(0));
+        assert_eq!(s.rfind_char('é'), Some(6));
+        assert_eq!(s.find_str("ba"), None);
+        assert_eq!(s.rfind_str("ba"), None);
+        assert_eq!(s.find_char('x'), None);
+        assert_eq!(s.rfind_char('x'), None);
+    }
+
+    #[test]
+    fn borrow_str_and_bytes() {
+        let s: StringWrapper<[u8; 8]> = StringWrapper::from_str("foo").unwrap();
+
+        let mut set = std::collections::HashSet::new();
+        set.insert(s);
+        assert!(set.contains("foo"));
+        assert!(!set.contains("bar"));
+
+        let mut tree = std::collections::BTreeSet::new();
+        tree.insert(s);
+        assert!(tree.contains(&b"foo"[..]));
+        assert!(!tree.contains(&b"bar"[..]));
+    }
+
+    #[test]
+    fn as_ref() {
+        fn takes_str(s: impl AsRef<str>) -> usize {
+            s.as_ref().len()
+        }
+        fn takes_bytes(b: impl AsRef<[u8]>) -> Vec<u8> {
+            b.as_ref().to_vec()
+        }
+
+        let s: StringWrapper<[u8; 8]> = StringWrapper::from_str("aé").unwrap();
+        assert_eq!(takes_str(s), 3);
+        assert_eq!(takes_bytes(s), b"a\xC3\xA9");
+    }
+
+    #[test]
+    fn retain_ascii() {
+        let mut s: StringWrapper<[u8; 16]> = StringWrapper::from_str("a1é2b🌠3").unwrap();
+        let mut seen = Vec::new();
+        s.retain_ascii(|b| {
+            seen.push(b);
+            !b.is_ascii_digit()
+        });
+        assert_eq!(&*s, "aéb🌠");
+        assert_eq!(seen, b"a12b3");
+    }
+
+    #[test]
+    fn char_count() {
+        let s: StringWrapper<[u8; 16]> = StringWrapper::from_str("aé☃🌠").unwrap();
+        assert_eq!(s.len(), 10);
+        assert_eq!(s.char_count(), 4);
+        assert_eq!(StringWrapper::new([0_u8; 4]).char_count(), 0);
+    }
+
+    #[test]
+    fn ensure_capacity() {
+        let mut s = StringWrapper::new([0_u8; 8]);
+        s.push_str("abc").unwrap();
+        assert_eq!(s.ensure_capacity(5), Ok(()));
+        assert_eq!(
+            s.ensure_capacity(6),
+            Err(Error::InsufficientLength {
+                expected: 9,
+                actual: 8
+            })
+        );
+    }
+
+    #[test]
+    fn try_format() {
+        let mut s = StringWrapper::new([0_u8; 8]);
+        s.push_str("x=").unwrap();
+        append_fmt!(s, "{}", 42).unwrap();
+        assert_eq!(&*s, "x=42");
+
+        assert_eq!(
+            append_fmt!(s, ", y={}", 12345),
+            Err(Error::InsufficientLength {
+                expected: 13,
+                actual: 8
+            })
+        );
+        assert_eq!(&*s, "x=42");
+    }
+
+    #[test]
+    fn push_char_lossy() {
+        let mut s = StringWrapper::new([0_u8; 5]);
+        assert!(s.push_char_lossy('a'));
+        assert!(s.push_char_lossy('☃'));
+        assert!(!s.push_char_lossy('é'));
+        assert_eq!(&*s, "a☃");
+        assert!(s.push_char_lossy('b'));
+        assert!(!s.push_char_lossy('c'));
+        assert_eq!(&*s, "a☃b");
+    }
+
+    #[test]
+    fn spare_capacity_mut_and_advance_len() {
+        use std::io::Read;
+
+        let mut s = StringWrapper::new([0_u8; 16]);
+        s.push_str("> ").unwrap();
+        let mut reader: &[u8] = "héllo".as_bytes();
+        let read = reader.read(s.spare_capacity_mut()).unwrap();
+        assert_eq!(read, 6);
+        unsafe { s.advance_len(read) };
+        assert_eq!(&*s, "> héllo");
+        assert_eq!(s.extra_capacity(), 8);
+    }
+
+    #[test]
+    fn borrowed_array_buffer() {
+        let mut array = [0_u8; 8];
+        {
+            let mut s = StringWrapper::new(&mut array);
+            s.push_str("abc").unwrap();
+            assert_eq!(&*s, "abc");
+            assert_eq!(s.capacity(), 8);
+        }
+        assert_eq!(&array[..3], b"abc");
+    }
+
+    #[test]
+    fn array_buffer() {
+        let s: StringWrapper<ArrayBuffer<4>> = StringWrapper::from_str("abcd").unwrap();
+        assert_eq!(&*s, "abcd");
+        assert_eq!(s.capacity(), 4);
+        assert_eq!(
+            StringWrapper::<ArrayBuffer<4>>::from_str("abcde"),
+            Err(Error::InsufficientLength {
+                expected: 5,
+                actual: 4
+            })
+        );
+    }
+
+    #[test]
+    fn char_indices_and_bytes() {
+        let s: StringWrapper<[u8; 8]> = StringWrapper::from_str("aé☃").unwrap();
+        let indices: Vec<(usize, char)> = s.char_indices().collect();
+        assert_eq!(indices, [(0, 'a'), (1, 'é'), (3, '☃')]);
+        assert!(s.bytes().eq("aé☃".bytes()));
+    }
+
+    #[test]
+    fn hash_map_lookup_by_str() {
+        let key: StringWrapper<[u8; 32]> = StringWrapper::from_str("key").unwrap();
+        // Required by the `Borrow<str>` contract for map lookups.
+        assert_eq!(hash(&key), hash("key"));
+
+        let mut map = std::collections::HashMap::new();
+        map.insert(key, 1);
+        map.insert(StringWrapper::from_str("ké").unwrap(), 2);
+        assert_eq!(map.get("key"), Some(&1));
+        assert_eq!(map.get("ké"), Some(&2));
+        assert_eq!(map.get("ke"), None);
+    }
+
+    #[test]
+    fn to_ascii_case() {
+        let s: StringWrapper<[u8; 16]> = StringWrapper::from_str("Straße Ünï 42").unwrap();
+        assert_eq!(&*s.to_ascii_uppercase(), "STRAßE ÜNï 42");
+        assert_eq!(&*s.to_ascii_lowercase(), "straße Ünï 42");
+        assert_eq!(&*s, "Straße Ünï 42");
+    }
+
+    #[test]
+    fn trim_in_place() {
+        let s: StringWrapper<[u8; 16]> = StringWrapper::from_str(" \t é ☃ a\n ").unwrap();
+
+        let mut start = s;
+        start.trim_start_in_place();
+        assert_eq!(&*start, "é ☃ a\n ");
+
+        let mut end = s;
+        end.trim_end_in_place();
+        assert_eq!(&*end, " \t é ☃ a");
+
+        let mut both = s;
+        both.trim_in_place();
+        assert_eq!(&*both, "é ☃ a");
+
+        let mut blank: StringWrapper<[u8; 4]> = StringWrapper::from_str("  ").unwrap();
+        blank.trim_in_place();
+        assert_eq!(&*blank, "");
+    }
+
+    #[test]
+    fn push_join() {
+        let mut s = StringWrapper::new([0_u8; 16]);
+        s.push_str("[").unwrap();
+        s.push_join(["a", "é", "☃"], ", ").unwrap();
+        assert_eq!(&*s, "[a, é, ☃");
+
+        let mut s = StringWrapper::new([0_u8; 16]);
+        s.push_join(Vec::<String>::new(), ", ").unwrap();
+        assert_eq!(&*s, "");
+    }
+
+    #[test]
+    fn push_join_partial_failure() {
+        let mut s = StringWrapper::new([0_u8; 8]);
+        assert_eq!(
+            s.push_join(vec!["ab".to_string(), "cd".into(), "ef".into()], ", "),
+            Err(Error::InsufficientLength {
+                expected: 10,
+                actual: 8
+            })
+        );
+        assert_eq!(&*s, "ab, cd");
+    }
+
+    #[cfg(feature = "use_serde")]
+    #[test]
+    fn deserialize_borrowed_and_owned() {
+        // serde_json hands out borrowed strings when there is nothing to unescape...
+        let s: StringWrapper<[u8; 8]> = serde_json::from_str("\"foo\"").unwrap();
+        assert_eq!(&*s, "foo");
+        // ...and transient ones otherwise.
+        let s: StringWrapper<[u8; 8]> = serde_json::from_str("\"a\\nb\"").unwrap();
+        assert_eq!(&*s, "a\nb");
+        let s: StringWrapper<[u8; 8]> = serde_json::from_reader("\"foo\"".as_bytes()).unwrap();
+        assert_eq!(&*s, "foo");
+
+        match serde_json::from_str::<StringWrapper<[u8; 3]>>("\"a\\n\\tb\"") {
+            Err(e) => assert_eq!(
+                format!("{}", e),
+                "invalid length 4, expected string that can fit into 3 bytes"
+            ),
+            Ok(x) => panic!("Expected error, got success: {:?}", x),
+        }
+    }
+
+    #[cfg(feature = "use_serde")]
+    #[test]
+    fn serde_as_bytes() {
+        let s: StringWrapper<[u8; 8]> = StringWrapper::from_str("aé").unwrap();
+        let encoded = bincode::serialize(&AsBytes(s)).unwrap();
+        assert_eq!(encoded, b"\x03\0\0\0\0\0\0\0a\xC3\xA9");
+        let decoded: AsBytes<StringWrapper<[u8; 8]>> = bincode::deserialize(&encoded).unwrap();
+        assert_eq!(decoded, AsBytes(s));
+
+        assert!(bincode::deserialize::<AsBytes<StringWrapper<[u8; 2]>>>(&encoded).is_err());
+
+        let invalid = b"\x01\0\0\0\0\0\0\0\xFF";
+        assert!(bincode::deserialize::<AsBytes<StringWrapper<[u8; 8]>>>(invalid).is_err());
+    }
+
+    #[test]
+    fn new_const() {
+        static EMPTY: StringWrapper<[u8; 16]> = StringWrapper::new_const([0_u8; 16]);
+        const TABLE: [StringWrapper<[u8; 4]>; 2] = [
+            StringWrapper::new_const(*b"abcd"),
+            StringWrapper::new_const([0_u8; 4]),
+        ];
+        assert_eq!(&*EMPTY, "");
+        assert_eq!(EMPTY.capacity(), 16);
+        assert_eq!(TABLE[0], TABLE[1]);
+    }
+
+    #[test]
+    fn copy_from_str() {
+        let s = StringWrapper::copy_from_str(vec![0_u8; 4], "aé").unwrap();
+        assert_eq!(&*s, "aé");
+        assert_eq!(s.capacity(), 4);
+
+        let s = StringWrapper::copy_from_str([0_u8; 3], "aé").unwrap();
+        assert_eq!(&*s, "aé");
+        assert_eq!(
+            StringWrapper::copy_from_str([0_u8; 2], "aé"),
+            Err(Error::InsufficientLength {
+                expected: 3,
+                actual: 2
+            })
+        );
+    }
+
+    #[test]
+    fn reinto() {
+        let mut s = StringWrapper::new([0_u8; 256]);
+        s.push_str("  aé  ").unwrap();
+        s.trim_in_place();
+        let small: StringWrapper<[u8; 3]> = s.reinto().unwrap();
+        assert_eq!(&*small, "aé");
+        assert_eq!(small.capacity(), 3);
+        assert_eq!(&*s, "aé");
+
+        assert_eq!(
+            s.reinto::<[u8; 2]>(),
+            Err(Error::InsufficientLength {
+                expected: 3,
+                actual: 2
+            })
+        );
+    }
+
+    #[test]
+    fn with_vec_capacity() {
+        let mut s = StringWrapper::with_vec_capacity(10);
+        assert_eq!(s.capacity(), 10);
+        s.push_str("abcdefgh").unwrap();
+        assert_eq!(s.push('é'), Ok(()));
+        assert_eq!(
+            s.push('!'),
+            Err(Error::InsufficientLength {
+                expected: 11,
+                actual: 10
+            })
+        );
+        assert_eq!(&*s, "abcdefghé");
+    }
+
+    #[test]
+    fn push_chars() {
+        let mut chars = "ab☃cd".chars().peekable();
+        let mut first = StringWrapper::new([0_u8; 4]);
+        assert_eq!(first.push_chars(&mut chars), 2);
+        assert_eq!(&*first, "ab");
+        assert_eq!(chars.peek(), Some(&'☃'));
+
+        let mut second = StringWrapper::new([0_u8; 4]);
+        assert_eq!(second.push_chars(&mut chars), 2);
+        assert_eq!(&*second, "☃c");
+
+        let mut third = StringWrapper::new([0_u8; 4]);
+        assert_eq!(third.push_chars(&mut chars), 1);
+        assert_eq!(&*third, "d");
+        assert_eq!(chars.next(), None);
+    }
+
+    #[test]
+    fn into_string() {
+        let s: StringWrapper<[u8; 8]> = StringWrapper::from_str("aé☃").unwrap();
+        let string: String = s.into_string();
+        assert_eq!(string, "aé☃");
+        assert_eq!(string.len(), s.len());
+    }
+
+    #[test]
+    fn repeat_char() {
+        let mut s = StringWrapper::new([0_u8; 10]);
+        s.push_str("a").unwrap();
+        s.repeat_char(' ', 3).unwrap();
+        s.repeat_char('☃', 2).unwrap();
+        s.repeat_char('x', 0).unwrap();
+        assert_eq!(&*s, "a   ☃☃");
+
+        assert_eq!(
+            s.repeat_char('é', 1),
+            Err(Error::InsufficientLength {
+                expected: 12,
+                actual: 10
+            })
+        );
+        assert_eq!(&*s, "a   ☃☃");
+    }
+
+    #[test]
+    fn pad_to() {
+        let s: StringWrapper<[u8; 16]> = StringWrapper::from_str("aé").unwrap();
+
+        let mut left = s;
+        left.pad_to(5, '·', Alignment::Left).unwrap();
+        assert_eq!(&*left, "aé···");
+
+        let mut right = s;
+        right.pad_to(5, '·', Alignment::Right).unwrap();
+        assert_eq!(&*right, "···aé");
+
+        let mut center = s;
+        center.pad_to(5, '·', Alignment::Center).unwrap();
+        assert_eq!(&*center, "·aé··");
+        assert_eq!(&*center, format!("{:·^5}", "aé"));
+
+        let mut wide = s;
+        wide.pad_to(1, '·', Alignment::Right).unwrap();
+        assert_eq!(&*wide, "aé");
+    }
+
+    #[test]
+    fn pad_to_overflow() {
+        let mut s: StringWrapper<[u8; 8]> = StringWrapper::from_str("aé").unwrap();
+        assert_eq!(
+            s.pad_to(4, '☃', Alignment::Right),
+            Err(Error::InsufficientLength {
+                expected: 9,
+                actual: 8
+            })
+        );
+        assert_eq!(&*s, "aé");
+    }
+
+    #[test]
+    fn from_char() {
+        for &(c, len) in &[('a', 1), ('é', 2), ('☃', 3), ('🌠', 4)] {
+            let s = StringWrapper::from(c);
+            assert_eq!(s.len(), len);
+            assert_eq!(&*s, c.to_string());
+        }
+    }
+
+    #[test]
+    fn try_from_bytes() {
+        let s = StringWrapper::<[u8; 4]>::try_from(&b"a\xC3\xA9"[..]).unwrap();
+        assert_eq!(&*s, "aé");
+
+        match StringWrapper::<[u8; 4]>::try_from(&b"a\xC3"[..]) {
+            Err(Error::InvalidUtf8(e)) => assert_eq!(e.valid_up_to(), 1),
+            other => panic!("Expected invalid UTF-8 error, got {:?}", other),
+        }
+
+        assert_eq!(
+            StringWrapper::<[u8; 2]>::try_from(&b"a\xC3\xA9"[..]),
+            Err(Error::InsufficientLength {
+                expected: 3,
+                actual: 2
+            })
+        );
+    }
+
+    #[test]
+    fn eq_ignore_ascii_case() {
+        let s: StringWrapper<[u8; 16]> = StringWrapper::from_str("Content-Type").unwrap();
+        assert!(s.eq_ignore_ascii_case("content-type"));
+        assert!(!s.eq_ignore_ascii_case("content-length"));
+
+        let other: StringWrapper<[u8; 32]> = StringWrapper::from_str("CONTENT-TYPE").unwrap();
+        assert!(s.eq_ignore_ascii_case(&other));
+
+        let non_ascii: StringWrapper<[u8; 4]> = StringWrapper::from_str("é").unwrap();
+        assert!(!non_ascii.eq_ignore_ascii_case("É"));
+    }
+
+    #[test]
+    fn split_str() {
+        let s: StringWrapper<[u8; 16]> = StringWrapper::from_str("é::☃::").unwrap();
+        let pieces: Vec<&str> = s.split_str("::").collect();
+        assert_eq!(pieces, ["é", "☃", ""]);
+    }
+
+    #[test]
+    fn push_line_and_lines() {
+        let mut s = StringWrapper::new([0_u8; 12]);
+        s.push_line("first").unwrap();
+        s.push_line("é").unwrap();
+        assert_eq!(
+            s.push_line("last"),
+            Err(Error::InsufficientLength {
+                expected: 14,
+                actual: 12
+            })
+        );
+        assert_eq!(&*s, "first\né\n");
+        let lines: Vec<&str> = s.lines().collect();
+        assert_eq!(lines, ["first", "é"]);
+    }
+
+    #[test]
+    fn test_stack_str() {
+        let s: StringWrapper<[u8; 5]> = stack_str!("héll");
+        assert_eq!(&*s, "héll");
+        assert_eq!(s.extra_capacity(), 0);
+
+        const GREETING: &str = "hi ☃";
+        let s: StringWrapper<[u8; 6]> = stack_str!(GREETING);
+        assert_eq!(&*s, GREETING);
+
+        const ABC: StringWrapper<[u8; 3]> = stack_str!("abc");
+        static ABC_STATIC: StringWrapper<[u8; 3]> = stack_str!("abc");
+        assert_eq!(&*ABC, "abc");
+        assert_eq!(ABC_STATIC, ABC);
+
+        const PADDED: StringWrapper<[u8; 8]> = StringWrapper::from_str_const("é");
+        assert_eq!(&*PADDED, "é");
+        assert_eq!(PADDED.extra_capacity(), 6);
+    }
+
+    #[test]
+    fn pop_str() {
+        let mut s: StringWrapper<[u8; 16]> = StringWrapper::from_str("ab☃🌠").unwrap();
+        assert_eq!(s.pop_str(4), "🌠");
+        assert_eq!(&*s, "ab☃");
+        assert_eq!(s.pop_str(0), "");
+        assert_eq!(s.pop_str(4), "b☃");
+        assert_eq!(&*s, "a");
+    }
+
+    #[test]
+    #[should_panic]
+    fn pop_str_not_char_boundary() {
+        let mut s: StringWrapper<[u8; 16]> = StringWrapper::from_str("ab☃").unwrap();
+        s.pop_str(2);
+    }
+
+    #[test]
+    fn wire_round_trip() {
+        let s: StringWrapper<[u8; 8]> = StringWrapper::from_str("aé").unwrap();
+        let mut wire = Vec::new();
+        s.to_wire(&mut wire).unwrap();
+        assert_eq!(wire, b"\0\x03a\xC3\xA9");
+
+        let mut cursor = std::io::Cursor::new(wire);
+        let s2: StringWrapper<[u8; 3]> = StringWrapper::from_wire(&mut cursor).unwrap();
+        assert_eq!(s2, StringWrapper::from_str("aé").unwrap());
+    }
+
+    #[test]
+    fn from_wire_errors() {
+        let mut too_long: &[u8] = b"\0\x03abc";
+        let err = StringWrapper::<[u8; 2]>::from_wire(&mut too_long).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+        let mut invalid: &[u8] = b"\0\x01\xFF";
+        let err = StringWrapper::<[u8; 2]>::from_wire(&mut invalid).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+        let mut truncated: &[u8] = b"\0\x02a";
+        let err = StringWrapper::<[u8; 2]>::from_wire(&mut truncated).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn cmp_different_buffers() {
+        let small: StringWrapper<[u8; 8]> = StringWrapper::from_str("aé").unwrap();
+        let large: StringWrapper<[u8; 16]> = StringWrapper::from_str("aé").unwrap();
+        let heap = StringWrapper::copy_from_str(vec![0_u8; 4], "b").unwrap();
+        assert!(small.eq_content(&large));
+        assert!(!small.eq_content(&heap));
+        assert_eq!(small.cmp_content(&large), cmp::Ordering::Equal);
+        assert_eq!(large.cmp_content(&heap), cmp::Ordering::Less);
+        assert_eq!(heap.cmp_content(&small), cmp::Ordering::Greater);
+    }
+
+    #[test]
+    fn encode_utf16_into() {
+        let s: StringWrapper<[u8; 8]> = StringWrapper::from_str("a😀").unwrap();
+        let mut out = [0_u16; 4];
+        assert_eq!(s.encode_utf16_into(&mut out), Ok(3));
+        assert_eq!(out, [0x61, 0xD83D, 0xDE00, 0]);
+
+        let mut short = [0_u16; 2];
+        assert_eq!(
+            s.encode_utf16_into(&mut short),
+            Err(Error::InsufficientLength {
+                expected: 3,
+                actual: 2
+            })
+        );
+        assert_eq!(short, [0, 0]);
+    }
+
+    #[test]
+    fn from_utf16() {
+        let s: StringWrapper<[u8; 8]> = StringWrapper::from_utf16(&[0x61, 0xD83D, 0xDE00]).unwrap();
+        assert_eq!(&*s, "a😀");
+
+        match StringWrapper::<[u8; 8]>::from_utf16(&[0x61, 0xD83D, 0x62]) {
+            Err(Error::InvalidUtf16(e)) => assert_eq!(e.unpaired_surrogate(), 0xD83D),
+            other => panic!("Expected invalid UTF-16 error, got {:?}", other),
+        }
+
+        assert_eq!(
+            StringWrapper::<[u8; 4]>::from_utf16(&[0x61, 0xD83D, 0xDE00]),
+            Err(Error::InsufficientLength {
+                expected: 5,
+                actual: 4
+            })
+        );
+    }
+
+    #[test]
+    fn strip_in_place() {
+        let mut s: StringWrapper<[u8; 16]> = StringWrapper::from_str("☃:aé:☃").unwrap();
+        assert!(!s.strip_prefix_in_place("a"));
+        assert!(!s.strip_suffix_in_place("a"));
+        assert_eq!(&*s, "☃:aé:☃");
+
+        assert!(s.strip_prefix_in_place("☃:"));
+        assert_eq!(&*s, "aé:☃");
+        assert!(s.strip_suffix_in_place(":☃"));
+        assert_eq!(&*s, "aé");
+    }
+
+    #[cfg(feature = "use_heapless")]
+    #[test]
+    fn heapless_buffer() {
+        let mut partial: heapless::Vec<u8, 8> = heapless::Vec::new();
+        partial.extend_from_slice(b"xy").unwrap();
+        assert_eq!(StringWrapper::new(partial.clone()).capacity(), 2);
+
+        let mut s = StringWrapper::from_heapless(partial);
+        assert_eq!(s.capacity(), 8);
+        s.push_str("aé").unwrap();
+        assert_eq!(&*s, "aé");
+
+        let owned: StringWrapper<heapless::Vec<u8, 4>> = StringWrapper::from_str("a☃").unwrap();
+        assert_eq!(&*owned, "a☃");
+        assert!(StringWrapper::<heapless::Vec<u8, 4>>::from_str("aé☃").is_err());
+    }
+
+    #[test]
+    fn split_into_frames() {
+        let s: StringWrapper<[u8; 16]> = StringWrapper::from_str("abé☃cd🌠").unwrap();
+        let frames: Vec<StringWrapper<[u8; 4]>> = s.split_into_frames().collect();
+        let frames: Vec<&str> = frames.iter().map(|f| &**f).collect();
+        assert_eq!(frames, ["abé", "☃c", "d", "🌠"]);
+
+        assert_eq!(
+            StringWrapper::new([0_u8; 4])
+                .split_into_frames::<[u8; 4]>()
+                .count(),
+            0
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "code point doesn't fit in an empty frame")]
+    fn split_into_frames_too_small() {
+        let s: StringWrapper<[u8; 4]> = StringWrapper::from_str("a☃").unwrap();
+        for _ in s.split_into_frames::<[u8; 2]>() {}
+    }
+
+    #[test]
+    fn byte_key_hash_map() {
+        let key: StringWrapper<[u8; 8]> = StringWrapper::from_str("aé").unwrap();
+        assert_eq!(hash(&ByteKey(key)), hash(&b"a\xC3\xA9"[..]));
+
+        let mut map = std::collections::HashMap::new();
+        map.insert(ByteKey(key), 1);
+        let bytes = b"a\xC3\xA9".to_vec();
+        assert_eq!(map.get(&bytes[..]), Some(&1));
+        assert_eq!(map.get(&b"a"[..]), None);
+    }
+
+    #[test]
+    fn get() {
+        let mut s: StringWrapper<[u8; 16]> = StringWrapper::from_str("aé☃").unwrap();
+        assert_eq!(s.get(1..3), Some("é"));
+        assert_eq!(s.get(..=2), Some("aé"));
+        assert_eq!(s.get(3..), Some("☃"));
+        assert_eq!(s.get(..), Some("aé☃"));
+        assert_eq!(s.get(6..), Some(""));
+
+        // Out of bounds, even though the backing buffer is larger.
+        assert_eq!(s.get(3..7), None);
+        assert_eq!(s.get(7..), None);
+        assert_eq!(s.get(..=usize::MAX), None);
+        // Not on a character boundary.
+        assert_eq!(s.get(2..), None);
+        assert_eq!(s.get(..4), None);
+
+        s.get_mut(..3).unwrap().make_ascii_uppercase();
+        assert_eq!(&*s, "Aé☃");
+        assert_eq!(s.get_mut(0..2), None);
+    }
+
+    #[test]
+    fn length_arithmetic_saturates() {
+        // A mock `Buffer` can't soundly report a capacity near `usize::MAX`: the capacity is the
+        // length of the slice `as_ref` returns, and slices are at most `isize::MAX` bytes, so
+        // `len` is too. `len + n` can then only overflow through a huge `n`, so this drives `n`
+        // to `usize::MAX` over a short buffer instead.
+        struct Short([u8; 4]);
+
+        unsafe impl Buffer for Short {
+            fn as_ref(&self) -> &[u8] {
+                &self.0
+            }
+            fn as_mut(&mut self) -> &mut [u8] {
+                &mut self.0
+            }
+        }
+
+        let mut s = StringWrapper::new(Short([0; 4]));
+        s.push_str("abc").unwrap();
+        assert_eq!(s.extra_capacity(), 1);
+        assert_eq!(
+            s.push('é'),
+            Err(Error::InsufficientLength {
+                expected: 5,
+                actual: 4,
+            })
+        );
+        assert_eq!(
+            s.push_str("éé"),
+            Err(Error::InsufficientLength {
+                expected: 4,
+                actual: 1,
+            })
+        );
+        assert_eq!(s.push_partial_str("éa"), Err(0));
+
+        let overflow = Err(Error::InsufficientLength {
+            expected: usize::MAX,
+            actual: 4,
+        });
+        assert_eq!(s.ensure_capacity(usize::MAX), overflow);
+        assert_eq!(s.repeat_char('é', usize::MAX / 2 + 1), overflow);
+        assert_eq!(s.pad_to(usize::MAX, '☃', Alignment::Left), overflow);
+        assert_eq!(&*s, "abc");
+        assert_eq!(s.extra_capacity(), 1);
+    }
+
+    #[test]
+    fn first_and_last_char() {
+        let s: StringWrapper<[u8; 8]> = StringWrapper::from_str("éa☃").unwrap();
+        assert_eq!(s.first_char(), Some('é'));
+        assert_eq!(s.last_char(), Some('☃'));
+
+        let empty = StringWrapper::new([0_u8; 8]);
+        assert_eq!(empty.first_char(), None);
+        assert_eq!(empty.last_char(), None);
+    }
+
+    #[test]
+    fn with_buffer_mut() {
+        let mut s: StringWrapper<[u8; 8]> = StringWrapper::from_str("aé-b").unwrap();
+        unsafe {
+            s.with_buffer_mut(|buffer, len| {
+                buffer[..len].make_ascii_uppercase();
+                buffer[len] = b'!';
+                len + 1
+            });
+        }
+        assert_eq!(&*s, "Aé-B!");
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic]
+    fn with_buffer_mut_invalid_utf8() {
+        let mut s: StringWrapper<[u8; 8]> = StringWrapper::from_str("aé").unwrap();
+        unsafe { s.with_buffer_mut(|_, len| len - 1) };
+    }
+
+    #[test]
+    fn rotate_chars() {
+        let s: StringWrapper<[u8; 16]> = StringWrapper::from_str("aé☃🌠").unwrap();
+
+        let mut left = s;
+        left.rotate_left_chars(2);
+        assert_eq!(&*left, "☃🌠aé");
+        left.rotate_left_chars(0);
+        assert_eq!(&*left, "☃🌠aé");
+        left.rotate_left_chars(4);
+        assert_eq!(&*left, "☃🌠aé");
+
+        let mut right = s;
+        right.rotate_right_chars(1);
+        assert_eq!(&*right, "🌠aé☃");
+        right.rotate_right_chars(0);
+        assert_eq!(&*right, "🌠aé☃");
+        right.rotate_right_chars(4);
+        assert_eq!(&*right, "🌠aé☃");
+    }
+
+    #[test]
+    #[should_panic]
+    fn rotate_too_many_chars() {
+        let mut s: StringWrapper<[u8; 16]> = StringWrapper::from_str("aé").unwrap();
+        s.rotate_right_chars(3);
+    }
+
+    #[test]
+    fn display_flags() {
+        let s: StringWrapper<[u8; 16]> = StringWrapper::from_str("aé☃🌠").unwrap();
+        assert_eq!(format!("{:>10}", s), "      aé☃🌠");
+        assert_eq!(format!("{:<6}|", s), "aé☃🌠  |");
+        assert_eq!(format!("{:-^8}", s), "--aé☃🌠--");
+        assert_eq!(format!("{:.3}", s), "aé☃");
+        assert_eq!(format!("{:>5.2}", s), "   aé");
+    }
+
+    #[test]
+    fn push_bytes() {
+        let mut s = StringWrapper::new([0_u8; 8]);
+        s.push_bytes(b"a").unwrap();
+        s.push_bytes("é☃".as_bytes()).unwrap();
+        assert_eq!(&*s, "aé☃");
+
+        match s.push_bytes(b"\xE2\x98") {
+            Err(Error::InvalidUtf8(_)) => {}
+            other => panic!("Expected invalid UTF-8 error, got {:?}", other),
+        }
+        match s.push_bytes(b"b\xFF") {
+            Err(Error::InvalidUtf8(e)) => assert_eq!(e.valid_up_to(), 1),
+            other => panic!("Expected invalid UTF-8 error, got {:?}", other),
+        }
+        assert!(s.push_bytes(b"bcd").is_err());
+        assert_eq!(&*s, "aé☃");
+    }
+
+    #[test]
+    fn stream_pusher() {
+        let mut pusher = StreamPusher::new(StringWrapper::new([0_u8; 16]));
+        pusher.push_incremental(b"a\xE2").unwrap();
+        assert_eq!(&**pusher.get_ref(), "a");
+        pusher.push_incremental(b"\x98").unwrap();
+        assert_eq!(&**pusher.get_ref(), "a");
+        pusher.push_incremental(b"\x83").unwrap();
+        assert_eq!(&**pusher.get_ref(), "a☃");
+        pusher.push_incremental(b"\xC3\xA9b\xF0\x9F").unwrap();
+        pusher.push_incremental(b"\x8C\xA0c").unwrap();
+        assert_eq!(&*pusher.finish().unwrap(), "a☃éb🌠c");
+    }
+
+    #[test]
+    fn stream_pusher_errors() {
+        let mut pusher = StreamPusher::new(StringWrapper::new([0_u8; 4]));
+        pusher.push_incremental(b"a\xE2").unwrap();
+        assert!(pusher.push_incremental(b"b").is_err());
+        assert!(pusher.push_incremental(b"\x98\x83\xFF").is_err());
+        assert_eq!(
+            pusher.push_incremental(b"\x98\x83b"),
+            Err(Error::InsufficientLength {
+                expected: 5,
+                actual: 4
+            })
+        );
+        assert_eq!(&**pusher.get_ref(), "a");
+        pusher.push_incremental(b"\x98").unwrap();
+        assert!(pusher.finish().is_err());
+    }
+
+    #[test]
+    fn remaining_and_write_char() {
+        use std::fmt::Write;
+
+        let mut s = StringWrapper::new([0_u8; 4]);
+        assert_eq!(s.remaining(), 4);
+        let c = 'a';
+        s.write_char('é').unwrap();
+        write!(s, "{}", c).unwrap();
+        assert_eq!(s.remaining(), 1);
+        assert_eq!(s.remaining(), s.extra_capacity());
+        assert!(s.write_char('é').is_err());
+        assert_eq!(&*s, "éa");
+    }
+
+    #[test]
+    fn dedup_whitespace() {
+        let mut s: StringWrapper<[u8; 16]> = StringWrapper::from_str("a   b\t\tc\né ").unwrap();
+        s.dedup_whitespace();
+        assert_eq!(&*s, "a b c é ");
+    }
+
+    #[test]
+    fn dedup_by() {
+        let mut s: StringWrapper<[u8; 16]> = StringWrapper::from_str("aaé☃☃☃éb").unwrap();
+        s.dedup_by(|a, b| a == b);
+        assert_eq!(&*s, "aé☃éb");
+
+        let mut s: StringWrapper<[u8; 16]> = StringWrapper::from_str("aAbBBa").unwrap();
+        s.dedup_by(|a, b| a.eq_ignore_ascii_case(&b));
+        assert_eq!(&*s, "aba");
+    }
+
+    #[test]
+    fn reverse_in_place() {
+        let mut s: StringWrapper<[u8; 16]> = StringWrapper::from_str("a😀b").unwrap();
+        s.reverse_in_place();
+        assert_eq!(&*s, "b😀a");
+        assert!(std::str::from_utf8(&s.buffer()[..s.len()]).is_ok());
+
+        let mut even: StringWrapper<[u8; 16]> = StringWrapper::from_str("aé☃🌠").unwrap();
+        even.reverse_in_place();
+        assert_eq!(&*even, "🌠☃éa");
+
+        let mut empty = StringWrapper::new([0_u8; 4]);
+        empty.reverse_in_place();
+        assert_eq!(&*empty, "");
+    }
+
+    #[test]
+    fn count_matches() {
+        let s: StringWrapper<[u8; 16]> = StringWrapper::from_str("aaaa, a-é-é").unwrap();
+        assert_eq!(s.count_matches("a"), 5);
+        assert_eq!(s.count_matches("aa"), 2);
+        assert_eq!(s.count_matches("aaa"), 1);
+        assert_eq!(s.count_matches("-é"), 2);
+        assert_eq!(s.count_matches("z"), 0);
+        assert_eq!(
+            s.match_indices_str("aa").collect::<Vec<_>>(),
+            [(0, "aa"), (2, "aa")]
+        );
+        assert_eq!(
+            s.match_indices_str("é").map(|(i, _)| i).collect::<Vec<_>>(),
+            [8, 11]
+        );
+        assert_eq!(s.match_indices_str("z").next(), None);
+    }
+
+    #[test]
+    fn push_str_sliding() {
+        let mut s = StringWrapper::new([0_u8; 6]);
+        s.push_str_sliding("éab");
+        assert_eq!(&*s, "éab");
+        // One byte short: the whole "é" goes.
+        s.push_str_sliding("cde");
+        assert_eq!(&*s, "abcde");
+        s.push_str_sliding("ü");
+        assert_eq!(&*s, "bcdeü");
+        s.push_str_sliding("f");
+        assert_eq!(&*s, "cdeüf");
+        s.push_str_sliding("gh");
+        assert_eq!(&*s, "eüfgh");
+        // Evicting "e" and only half of "ü" isn't possible, so all of "ü" goes too.
+        s.push_str_sliding("xy");
+        assert_eq!(&*s, "fghxy");
+
+        // Longer than the capacity: keep the suffix starting on a code point boundary.
+        s.push_str_sliding("012€3456");
+        assert_eq!(&*s, "3456");
+        s.push_str_sliding("0123€45");
+        assert_eq!(&*s, "3€45");
+        s.push_str_sliding("xyz0123456789");
+        assert_eq!(&*s, "456789");
+    }
+
+    #[test]
+    fn char_ngrams() {
+        let s: StringWrapper<[u8; 8]> = StringWrapper::from_str("café").unwrap();
+        assert_eq!(s.char_ngrams(2).collect::<Vec<_>>(), ["ca", "af", "fé"]);
+        assert_eq!(s.char_ngrams(3).collect::<Vec<_>>(), ["caf", "afé"]);
+        assert_eq!(s.char_ngrams(4).collect::<Vec<_>>(), ["café"]);
+        assert_eq!(s.char_ngrams(5).next(), None);
+        assert_eq!(s.char_ngrams(1).last(), Some("é"));
+        assert_eq!(StringWrapper::new([0_u8; 4]).char_ngrams(1).next(), None);
+    }
+
+    #[test]
+    fn replace_char_in_place() {
+        let mut s: StringWrapper<[u8; 16]> = StringWrapper::from_str("a-b-é-c").unwrap();
+        s.replace_char_in_place('-', '_').unwrap();
+        assert_eq!(&*s, "a_b_é_c");
+        s.replace_char_in_place('é', 'ü').unwrap();
+        assert_eq!(&*s, "a_b_ü_c");
+        s.replace_char_in_place('z', 'y').unwrap();
+        assert_eq!(&*s, "a_b_ü_c");
+
+        assert_eq!(
+            s.replace_char_in_place('_', 'é'),
+            Err(Error::CharLengthMismatch { from: 1, to: 2 })
+        );
+        assert_eq!(
+            s.replace_char_in_place('ü', '_'),
+            Err(Error::CharLengthMismatch { from: 2, to: 1 })
+        );
+        assert_eq!(&*s, "a_b_ü_c");
+    }
+
+    #[test]
+    fn read_only() {
+        let bytes = "héllo".as_bytes();
+        let s = ReadOnlyStringWrapper::new(bytes).unwrap();
+        assert_eq!(s.len(), 6);
+        assert!(!s.is_empty());
+        assert!(ReadOnlyStringWrapper::default().is_empty());
+        assert_eq!(&*s, "héllo");
+        assert_eq!(s.as_str(), "héllo");
+        assert_eq!(s.find('l'), Some(3));
+        assert_eq!(format!("{:>7}|{:?}", s, s), "  héllo|\"héllo\"");
+        assert_eq!(hash(&s), hash("héllo"));
+
+        assert_eq!(
+            ReadOnlyStringWrapper::new(b"\xffab").map_err(|e| match e {
+                Error::InvalidUtf8(e) => e.valid_up_to(),
+                _ => panic!(),
+            }),
+            Err(0)
+        );
+
+        let owned: StringWrapper<[u8; 8]> = StringWrapper::from_str("héllo").unwrap();
+        assert_eq!(owned.as_read_only(), s);
+        assert!(s == owned);
+        assert!(owned.as_read_only() == s);
+        let other = ReadOnlyStringWrapper::from("i");
+        assert!(other > s);
+        assert!(owned.as_read_only() < other);
+        assert!(other > owned);
+        assert_eq!(s.partial_cmp(&owned), Some(cmp::Ordering::Equal));
+    }
+
+    #[test]
+    fn as_ptr() {
+        let mut s: StringWrapper<[u8; 8]> = StringWrapper::from_str("héllo").unwrap();
+        assert_eq!(s.as_ptr(), s.buffer().as_ptr());
+        let bytes = unsafe { std::slice::from_raw_parts(s.as_ptr(), s.len()) };
+        assert_eq!(bytes, "héllo".as_bytes());
+
+        unsafe { *s.as_mut_ptr() = b'j' };
+        assert_eq!(&*s, "jéllo");
+    }
+
+    #[test]
+    fn as_cstr() {
+        let mut s: StringWrapper<[u8; 8]> = StringWrapper::from_str("héllo").unwrap();
+        assert_eq!(
+            s.as_cstr().unwrap().to_bytes_with_nul(),
+            "héllo\0".as_bytes()
+        );
+        assert_eq!(s.len(), 6);
+        assert_eq!(&*s, "héllo");
+
+        let mut nul: StringWrapper<[u8; 8]> = StringWrapper::from_str("ab\0c").unwrap();
+        assert_eq!(nul.as_cstr(), Err(Error::InteriorNul { position: 2 }));
+
+        let mut full: StringWrapper<[u8; 4]> = StringWrapper::from_str("abcd").unwrap();
+        assert_eq!(
+            full.as_cstr(),
+            Err(Error::InsufficientLength {
+                expected: 5,
+                actual: 4
+            })
+        );
+    }
+
+    #[test]
+    fn saturating_push_str() {
+        let mut s = StringWrapper::new([0_u8; 5]);
+        assert!(s.saturating_push_str("ab"));
+        assert!(!s.saturating_push_str("c€"));
+        assert_eq!(&*s, "abc");
+        assert!(!s.saturating_push_str("€"));
+        assert_eq!(&*s, "abc");
+        assert!(s.saturating_push_str(""));
+        assert!(s.saturating_push_str("de"));
+        assert_eq!(&*s, "abcde");
+    }
+
+    #[test]
+    fn ellipsize() {
+        let mut s: StringWrapper<[u8; 16]> = StringWrapper::from_str("héllo").unwrap();
+        s.ellipsize(5);
+        assert_eq!(&*s, "héllo");
+        s.ellipsize(4);
+        assert_eq!(&*s, "hél…");
+        s.ellipsize(1);
+        assert_eq!(&*s, "…");
+        s.ellipsize(0);
+        assert_eq!(&*s, "");
+
+        // Dropping one code point doesn't free 3 bytes, so more have to go: "aaé…" would be 7
+        // bytes, and cutting at 3 would split the "é".
+        let mut full: StringWrapper<[u8; 6]> = StringWrapper::from_str("aaéaa").unwrap();
+        full.ellipsize(5);
+        assert_eq!(&*full, "aaéaa");
+        full.ellipsize(4);
+        assert_eq!(&*full, "aa…");
+        assert_eq!(full.len(), 5);
 
-    use StringWrapper;
+        let mut full: StringWrapper<[u8; 12]> = StringWrapper::from_str("éééééé").unwrap();
+        full.ellipsize(5);
+        assert_eq!(&*full, "éééé…");
+        assert_eq!(full.char_count(), 5);
 
-    #[test]
-    fn traits() {
-        // A simple way to ensure that Eq is implemented for StringWrapper
-        #[derive(Eq, PartialEq, Ord, PartialOrd)]
-        struct Foo {
-            x: StringWrapper<[u8; 64]>,
-        }
+        let mut tiny: StringWrapper<[u8; 2]> = StringWrapper::from_str("ab").unwrap();
+        tiny.ellipsize(1);
+        assert_eq!(&*tiny, "");
     }
 
     #[test]
-    #[allow(clippy::eq_op)]
-    fn eq() {
-        let mut s = StringWrapper::<[u8; 3]>::new(*b"000");
-        assert_eq!(s, s);
-        s.push_str("foo").unwrap();
-        let mut s2 = StringWrapper::<[u8; 3]>::new(*b"000");
-        s2.push_str("foo").unwrap();
-        assert_eq!(s, s2);
+    fn aligned_buffers() {
+        fn check<B: OwnedBuffer>(align: usize) {
+            assert_eq!(std::mem::align_of::<B>(), align);
+            let sws: [StringWrapper<B>; 3] = [
+                StringWrapper::from_str("a").unwrap(),
+                StringWrapper::from_str("bc").unwrap(),
+                StringWrapper::from_str("déf").unwrap(),
+            ];
+            for sw in &sws {
+                assert_eq!(sw.as_ptr() as usize % align, 0);
+            }
+            assert_eq!(&*sws[2], "déf");
+            assert_eq!(sws[2].capacity(), 5);
+        }
+        check::<Aligned4<[u8; 5]>>(4);
+        check::<Aligned8<[u8; 5]>>(8);
+        check::<Aligned16<[u8; 5]>>(16);
+        check::<Aligned32<[u8; 5]>>(32);
+        check::<Aligned64<[u8; 5]>>(64);
 
-        let mut s3 = StringWrapper::<[u8; 3]>::new(*b"000");
-        s3.push_str("bar").unwrap();
-        assert_ne!(s, s3);
+        let s: StringWrapper<Aligned64<[u8; 256]>> = StringWrapper::from_str("x").unwrap();
+        assert_eq!(std::mem::align_of_val(&s), 64);
+        assert_eq!(s.capacity(), 256);
     }
 
     #[test]
-    fn eq_only_to_length() {
-        let a = StringWrapper::<[u8; 5]>::new(*b"aaaaa");
-        let b = StringWrapper::<[u8; 5]>::new(*b"bbbbb");
-        assert_eq!(a, b);
+    fn shift_left() {
+        let mut s: StringWrapper<[u8; 16]> = StringWrapper::from_str("é☃🌠x").unwrap();
+        s.shift_left(0);
+        assert_eq!(&*s, "é☃🌠x");
+        s.shift_left(2);
+        assert_eq!(&*s, "☃🌠x");
+        s.shift_left(3);
+        assert_eq!(&*s, "🌠x");
+        assert!(std::str::from_utf8(&s.buffer()[..s.len()]).is_ok());
+        s.shift_left(5);
+        assert_eq!(&*s, "");
     }
 
     #[test]
-    fn ord() {
-        let mut s = StringWrapper::<[u8; 3]>::new(*b"000");
-        let mut s2 = StringWrapper::<[u8; 3]>::new(*b"000");
-        s.push_str("a").unwrap();
-        s2.push_str("b").unwrap();
-        assert_eq!(s.partial_cmp(&s2), Some(cmp::Ordering::Less));
-        assert_eq!(s.cmp(&s2), cmp::Ordering::Less);
+    #[should_panic]
+    fn shift_left_inside_char() {
+        let mut s: StringWrapper<[u8; 4]> = StringWrapper::from_str("éa").unwrap();
+        s.shift_left(1);
     }
 
+    #[cfg(feature = "use_unicode_segmentation")]
     #[test]
-    fn ord_only_to_length() {
-        let mut s = StringWrapper::<[u8; 3]>::new(*b"000");
-        let mut s2 = StringWrapper::<[u8; 3]>::new(*b"111");
-        assert_eq!(s.partial_cmp(&s2), Some(cmp::Ordering::Equal));
-        assert_eq!(s.cmp(&s2), cmp::Ordering::Equal);
+    fn truncate_graphemes() {
+        let family = "👨\u{200d}👩\u{200d}👧";
+        let mut s: StringWrapper<[u8; 64]> = StringWrapper::from_str("a").unwrap();
+        s.push_str(family).unwrap();
+        s.push_str("e\u{301}b").unwrap();
+        assert_eq!(s.char_count(), 9);
 
-        s.push_str("aa").unwrap();
-        s2.push_str("aa").unwrap();
-        assert_eq!(s.partial_cmp(&s2), Some(cmp::Ordering::Equal));
-        assert_eq!(s.cmp(&s2), cmp::Ordering::Equal);
+        s.truncate_graphemes(4);
+        assert_eq!(s.len(), 1 + family.len() + 4);
+        s.truncate_graphemes(3);
+        assert_eq!(s.len(), 1 + family.len() + 3);
+        s.truncate_graphemes(2);
+        assert_eq!(&s[1..], family);
+        s.truncate_graphemes(1);
+        assert_eq!(&*s, "a");
+        s.truncate_graphemes(0);
+        assert_eq!(&*s, "");
     }
 
-    #[cfg(test)]
-    fn hash<T: hash::Hash>(t: &T) -> u64 {
-        // who knows why this isn't in std
-        let mut h = std::collections::hash_map::DefaultHasher::new();
-        t.hash(&mut h);
-        hash::Hasher::finish(&h)
+    #[test]
+    fn concat_into() {
+        let a: StringWrapper<[u8; 4]> = StringWrapper::from_str("aé").unwrap();
+        let b: StringWrapper<[u8; 4]> = StringWrapper::from_str("bcde").unwrap();
+        let ab: StringWrapper<[u8; 8]> = StringWrapper::concat_into(&a, &b).unwrap();
+        assert_eq!(&*ab, "aébcde");
+        assert_eq!(
+            StringWrapper::<[u8; 6]>::concat_into(&b, &b),
+            Err(Error::InsufficientLength {
+                expected: 8,
+                actual: 6
+            })
+        );
     }
 
     #[test]
-    fn hash_only_to_length() {
-        let mut s = StringWrapper::<[u8; 64]>::new([0_u8; 64]);
-        let mut s2 = StringWrapper::<[u8; 64]>::new([1_u8; 64]);
-        assert_eq!(hash(&s), hash(&s2));
-        s.push_str("a").unwrap();
-        assert!(hash(&s) != hash(&s2));
-        s2.push_str("a").unwrap();
-        assert_eq!(hash(&s), hash(&s2));
+    fn try_truncate() {
+        let mut s: StringWrapper<[u8; 8]> = StringWrapper::from_str("aéb").unwrap();
+        assert_eq!(s.try_truncate(5), Err(Error::NotCharBoundary { index: 5 }));
+        assert_eq!(s.try_truncate(2), Err(Error::NotCharBoundary { index: 2 }));
+        assert_eq!(&*s, "aéb");
+        s.try_truncate(3).unwrap();
+        assert_eq!(&*s, "aé");
+        s.try_truncate(1).unwrap();
+        assert_eq!(&*s, "a");
+        s.try_truncate(0).unwrap();
+        assert_eq!(&*s, "");
     }
 
     #[test]
-    fn from_str() {
-        let s: StringWrapper<[u8; 64]> = StringWrapper::from_str("OMG!").unwrap();
-        let mut s2 = StringWrapper::new([0_u8; 64]);
-        s2.push_str("OMG!").unwrap();
-        assert_eq!(s, s2);
+    fn stack_size() {
+        assert_eq!(
+            StringWrapper::<[u8; 24]>::STACK_SIZE,
+            24 + std::mem::size_of::<usize>()
+        );
+        assert_eq!(
+            StringWrapper::<[u8; 5]>::STACK_SIZE,
+            std::mem::size_of::<StringWrapper<[u8; 5]>>()
+        );
+        assert!(StringWrapper::<[u8; 5]>::STACK_SIZE >= 5 + std::mem::size_of::<usize>());
+        assert!(!StringWrapper::<[u8; 25]>::fits_on_stack(32));
     }
 
     #[test]
-    fn it_works() {
-        let mut s = StringWrapper::new([0; 10]);
-        assert_eq!(&*s, "");
-        assert_eq!(s.len(), 0);
-        assert_eq!(s.capacity(), 10);
-        assert_eq!(s.extra_capacity(), 10);
+    fn word_count() {
+        let s: StringWrapper<[u8; 32]> = StringWrapper::from_str("  héllo   wörld\t☃ \n").unwrap();
+        assert_eq!(
+            s.split_whitespace().collect::<Vec<_>>(),
+            ["héllo", "wörld", "☃"]
+        );
+        assert_eq!(s.word_count(), 3);
+        // U+3000 IDEOGRAPHIC SPACE is whitespace too.
+        let s: StringWrapper<[u8; 16]> = StringWrapper::from_str("日本\u{3000}語").unwrap();
+        assert_eq!(s.word_count(), 2);
+        let s: StringWrapper<[u8; 4]> = StringWrapper::from_str(" \t ").unwrap();
+        assert_eq!(s.word_count(), 0);
+    }
 
-        assert_eq!(&*s, "");
-        assert_eq!(s.len(), 0);
-        assert_eq!(s.capacity(), 10);
-        assert_eq!(s.extra_capacity(), 10);
+    #[test]
+    fn write_char_at_boundary() {
+        use std::fmt::Write;
 
-        s.push_str("a").unwrap();
-        assert_eq!(&*s, "a");
-        assert_eq!(s.len(), 1);
-        assert_eq!(s.capacity(), 10);
-        assert_eq!(s.extra_capacity(), 9);
+        let (a, nul, star, grin) = ('a', '\0', '🌠', '😀');
+        let mut s = StringWrapper::new([0_u8; 6]);
+        write!(s, "{}{}", a, nul).unwrap();
+        assert_eq!(&*s, "a\0");
+        // 4 bytes fit exactly.
+        write!(s, "{}", star).unwrap();
+        assert_eq!(&*s, "a\0🌠");
+        assert_eq!(s.extra_capacity(), 0);
 
-        assert_eq!(s.push('é'), Ok(()));
+        // A 4-byte char in a 2-byte gap is rejected as a whole.
+        let mut s = StringWrapper::new([0_u8; 4]);
+        write!(s, "{}{}", a, nul).unwrap();
+        assert!(write!(s, "{}", grin).is_err());
+        assert!(s.write_char(grin).is_err());
+        assert_eq!(&*s, "a\0");
+        assert_eq!(s.extra_capacity(), 2);
+    }
+
+    #[test]
+    fn try_from_chars() {
+        let s: StringWrapper<[u8; 4]> = StringWrapper::try_from_chars("aé".chars()).unwrap();
         assert_eq!(&*s, "aé");
-        assert_eq!(s.len(), 3);
-        assert_eq!(s.capacity(), 10);
-        assert_eq!(s.extra_capacity(), 7);
+        let s: StringWrapper<[u8; 4]> = StringWrapper::try_from_chars(vec!['x'; 4]).unwrap();
+        assert_eq!(&*s, "xxxx");
+        assert_eq!(
+            StringWrapper::<[u8; 4]>::try_from_chars("aéü".chars()),
+            Err(Error::InsufficientLength {
+                expected: 5,
+                actual: 4
+            })
+        );
+    }
 
-        assert_eq!(s.push_partial_str("~~~"), Ok(()));
-        assert_eq!(&*s, "aé~~~");
-        assert_eq!(s.len(), 6);
-        assert_eq!(s.capacity(), 10);
-        assert_eq!(s.extra_capacity(), 4);
+    #[test]
+    fn insert_str() {
+        let mut s: StringWrapper<[u8; 8]> = StringWrapper::from_str("aéb").unwrap();
+        s.insert_str(1, "xy").unwrap();
+        assert_eq!(&*s, "axyéb");
+        s.insert_str(6, "!").unwrap();
+        s.insert_str(0, "").unwrap();
+        assert_eq!(&*s, "axyéb!");
+        assert_eq!(
+            s.insert_str(0, "123"),
+            Err(Error::InsufficientLength {
+                expected: 10,
+                actual: 8
+            })
+        );
+        assert_eq!(&*s, "axyéb!");
+    }
 
-        assert_eq!(s.push_partial_str("hello"), Err(4));
-        assert_eq!(&*s, "aé~~~hell");
-        assert_eq!(s.len(), 10);
-        assert_eq!(s.capacity(), 10);
-        assert_eq!(s.extra_capacity(), 0);
+    #[test]
+    fn writer_at() {
+        use std::fmt::Write;
 
-        s.truncate(6);
-        assert_eq!(&*s, "aé~~~");
-        assert_eq!(s.len(), 6);
-        assert_eq!(s.capacity(), 10);
-        assert_eq!(s.extra_capacity(), 4);
+        let mut s: StringWrapper<[u8; 32]> = StringWrapper::from_str("héllo, !").unwrap();
+        let placeholder = s.find_char('!').unwrap();
+        {
+            let (name, n) = ("wörld", 42);
+            let mut w: Writer<_> = s.writer_at(placeholder);
+            write!(w, "{} ", name).unwrap();
+            write!(w, "#{}", n).unwrap();
+            assert_eq!(w.position(), placeholder + 10);
+        }
+        assert_eq!(&*s, "héllo, wörld #42!");
 
-        assert_eq!(s.push_partial_str("_🌠"), Err(1));
-        assert_eq!(&*s, "aé~~~_");
-        assert_eq!(s.len(), 7);
-        assert_eq!(s.capacity(), 10);
-        assert_eq!(s.extra_capacity(), 3);
+        // Overflow leaves whatever was inserted before it.
+        let mut small: StringWrapper<[u8; 6]> = StringWrapper::from_str("[]").unwrap();
+        let (ab, cde) = ("ab", "cde");
+        assert!(write!(small.writer_at(1), "{}{}", ab, cde).is_err());
+        assert_eq!(&*small, "[ab]");
+    }
 
+    #[test]
+    fn is_char_boundary() {
+        let s: StringWrapper<[u8; 16]> = StringWrapper::from_str("aé🌠").unwrap();
+        let boundaries: Vec<usize> = (0..10).filter(|&i| s.is_char_boundary(i)).collect();
+        assert_eq!(boundaries, [0, 1, 3, 7]);
+        // Past the end of the string, even within the capacity.
+        assert!(!s.is_char_boundary(8));
+        assert!(!s.is_char_boundary(16));
+        assert!(StringWrapper::new([0_u8; 4]).is_char_boundary(0));
+    }
+
+    #[test]
+    fn push_str_all() {
+        let mut s = StringWrapper::new([0_u8; 8]);
+        s.push_str_all(&["a", "", "é", "bc"]).unwrap();
+        assert_eq!(&*s, "aébc");
+        s.push_str_all(&[]).unwrap();
         assert_eq!(
-            s.push('🌠'),
+            s.push_str_all(&["12", "34", "5"]),
             Err(Error::InsufficientLength {
-                expected: 11,
-                actual: 10
+                expected: 10,
+                actual: 8
             })
         );
-        assert_eq!(&*s, "aé~~~_");
-        assert_eq!(s.len(), 7);
-        assert_eq!(s.capacity(), 10);
-        assert_eq!(s.extra_capacity(), 3);
+        assert_eq!(&*s, "aébc");
+        s.push_str_all(&["1", "é"]).unwrap();
+        assert_eq!(&*s, "aébc1é");
+        assert_eq!(s.extra_capacity(), 0);
+    }
 
-        let buffer: [u8; 10] = s.into_buffer();
-        assert_eq!(&buffer, b"a\xC3\xA9~~~_ell");
-        assert_eq!(format!("{}", s), "aé~~~_");
-        assert_eq!(format!("{:?}", s), r#""aé~~~_""#);
+    #[test]
+    fn keep_range() {
+        let mut s: StringWrapper<[u8; 16]> = StringWrapper::from_str("ab☃éc🌠d").unwrap();
+        s.keep_range(2..);
+        assert_eq!(&*s, "☃éc🌠d");
+        s.keep_range(..=9);
+        assert_eq!(&*s, "☃éc🌠");
+        s.keep_range(3..6);
+        assert_eq!(&*s, "éc");
+        assert!(std::str::from_utf8(&s.buffer()[..s.len()]).is_ok());
+        s.keep_range(..);
+        assert_eq!(&*s, "éc");
+        s.keep_range(3..3);
+        assert_eq!(&*s, "");
+    }
 
-        assert_eq!(s.push_partial_str("ô!?"), Err(3));
-        assert_eq!(&*s, "aé~~~_ô!");
-        assert_eq!(s.len(), 10);
-        assert_eq!(s.capacity(), 10);
-        assert_eq!(s.extra_capacity(), 0);
+    #[test]
+    #[should_panic]
+    fn keep_range_inside_char() {
+        let mut s: StringWrapper<[u8; 8]> = StringWrapper::from_str("aéb").unwrap();
+        s.keep_range(0..2);
     }
 
-    #[cfg(feature = "use_serde")]
     #[test]
-    fn test_serde() {
-        let mut s = StringWrapper::new([0u8; 64]);
-        s.push_str("foobar").unwrap();
-        let json = serde_json::to_string(&s).unwrap();
-        assert_eq!(json, "\"foobar\"");
-        let s2 = serde_json::from_str(&json).unwrap();
-        assert_eq!(s, s2);
+    fn push_str_expect() {
+        let mut s = StringWrapper::new([0_u8; 4]);
+        s.push_str_expect("aé");
+        s.push_str_expect("b");
+        assert_eq!(&*s, "aéb");
     }
 
-    #[cfg(feature = "use_serde")]
     #[test]
-    fn deserialize_too_long() {
-        let json = "\"12345\"";
-        match serde_json::from_str::<StringWrapper<[u8; 3]>>(&json) {
-            Err(e) => {
-                assert_eq!(
-                    format!("{}", e),
-                    "invalid length 5, expected string that can fit into 3 bytes"
-                )
+    #[should_panic(expected = "push_str_expect: 2 bytes needed but only 1 available")]
+    fn push_str_expect_overflow() {
+        let mut s = StringWrapper::new([0_u8; 4]);
+        s.push_str_expect("abc");
+        s.push_str_expect("é");
+    }
+
+    #[test]
+    fn swap() {
+        let mut front: StringWrapper<[u8; 8]> = StringWrapper::from_str("frönt").unwrap();
+        let mut back: StringWrapper<[u8; 8]> = StringWrapper::from_str("bk").unwrap();
+        front.swap(&mut back);
+        assert_eq!(&*front, "bk");
+        assert_eq!(&*back, "frönt");
+        assert_eq!(front.extra_capacity(), 6);
+
+        let mut a = StringWrapper::with_vec_capacity(4);
+        let mut b = StringWrapper::with_vec_capacity(16);
+        a.push_str("a").unwrap();
+        b.push_str("b").unwrap();
+        a.swap(&mut b);
+        assert_eq!((&*a, a.capacity()), ("b", 16));
+        assert_eq!((&*b, b.capacity()), ("a", 4));
+    }
+
+    #[test]
+    fn char_at() {
+        let s: StringWrapper<[u8; 8]> = StringWrapper::from_str("aé🌠").unwrap();
+        assert_eq!(s.char_at(0), Some('a'));
+        assert_eq!(s.char_at(1), Some('é'));
+        assert_eq!(s.char_at(2), None);
+        assert_eq!(s.char_at(3), Some('🌠'));
+        assert_eq!(s.char_at(5), None);
+        assert_eq!(s.char_at(7), None);
+        assert_eq!(s.char_at(8), None);
+    }
+
+    #[test]
+    fn cursor_movement() {
+        let s: StringWrapper<[u8; 8]> = StringWrapper::from_str("aé🌠").unwrap();
+        assert_eq!(s.char_len_at(0), Some(1));
+        assert_eq!(s.char_len_at(1), Some(2));
+        assert_eq!(s.char_len_at(3), Some(4));
+        assert_eq!(s.char_len_at(4), None);
+        assert_eq!(s.char_len_at(7), None);
+
+        let mut forward = vec![0];
+        while let Some(i) = s.next_char_boundary(*forward.last().unwrap()) {
+            forward.push(i);
+        }
+        assert_eq!(forward, [0, 1, 3, 7]);
+        let mut backward = vec![7];
+        while let Some(i) = s.prev_char_boundary(*backward.last().unwrap()) {
+            backward.push(i);
+        }
+        assert_eq!(backward, [7, 3, 1, 0]);
+
+        // From inside a code point.
+        assert_eq!(s.next_char_boundary(4), Some(7));
+        assert_eq!(s.prev_char_boundary(5), Some(3));
+        assert_eq!(s.prev_char_boundary(8), None);
+    }
+
+    #[test]
+    fn retain_indexed() {
+        let mut s: StringWrapper<[u8; 16]> = StringWrapper::from_str("aé☃b🌠cü").unwrap();
+        s.retain_indexed(|i, _| i % 2 == 1);
+        assert_eq!(&*s, "ébc");
+        let mut s: StringWrapper<[u8; 16]> = StringWrapper::from_str("aé☃b🌠cü").unwrap();
+        s.retain_indexed(|i, c| i >= 3 && c != 'c');
+        assert_eq!(&*s, "b🌠ü");
+    }
+
+    #[test]
+    fn map_ascii_bytes() {
+        fn rot13(b: u8) -> u8 {
+            match b {
+                b'a'..=b'z' => (b - b'a' + 13) % 26 + b'a',
+                b'A'..=b'Z' => (b - b'A' + 13) % 26 + b'A',
+                _ => b,
             }
-            Ok(x) => panic!("Expected error, got success: {:?}", x),
         }
+        let mut s: StringWrapper<[u8; 32]> = StringWrapper::from_str("Héllo, Wörld! 42").unwrap();
+        s.map_ascii_bytes(rot13);
+        assert_eq!(&*s, "Uéyyb, Jöeyq! 42");
+        s.map_ascii_bytes(rot13);
+        assert_eq!(&*s, "Héllo, Wörld! 42");
+
+        // Results that aren't ASCII are discarded, and `f` never sees non-ASCII bytes.
+        s.map_ascii_bytes(|b| {
+            assert!(b.is_ascii());
+            if b == b'!' {
+                0xff
+            } else {
+                b.to_ascii_uppercase()
+            }
+        });
+        assert_eq!(&*s, "HéLLO, WöRLD! 42");
     }
 
-    #[allow(clippy::clone_on_copy)]
     #[test]
-    fn clone() {
-        let s = StringWrapper::new([0_u8; 64]);
-        let y: StringWrapper<[u8; 64]> = s.clone();
-        println!("s: {}, y: {}", s, y);
+    fn fill_repeating() {
+        let mut s: StringWrapper<[u8; 5]> = StringWrapper::from_str("ab").unwrap();
+        s.fill_repeating();
+        assert_eq!(&*s, "ababa");
+
+        // "aé" is 3 bytes: 3 whole copies, then only the "a" of the 4th fits in the last 2 bytes.
+        let mut s: StringWrapper<[u8; 11]> = StringWrapper::from_str("aé").unwrap();
+        s.fill_repeating();
+        assert_eq!(&*s, "aéaéaéa");
+        assert_eq!(s.extra_capacity(), 1);
+
+        let mut s: StringWrapper<[u8; 7]> = StringWrapper::from_str("€").unwrap();
+        s.fill_repeating();
+        assert_eq!(&*s, "€€");
+
+        let mut empty = StringWrapper::new([0_u8; 4]);
+        empty.fill_repeating();
+        assert_eq!(&*empty, "");
     }
 
     #[test]
-    fn test_stack_format() {
-        let s = stack_format!(128, "{}{}{}", 1, 2, 3).unwrap();
-        assert_eq!(&*s, "123");
+    fn ignore_bytes() {
+        let sep = b"-_ ";
+        let a: StringWrapper<[u8; 16]> = StringWrapper::from_str("a-b_c d").unwrap();
+        let b: StringWrapper<[u8; 16]> = StringWrapper::from_str("abcd").unwrap();
+        let c: StringWrapper<[u8; 16]> = StringWrapper::from_str("ab--ce").unwrap();
+        assert_eq!(IgnoreBytes(&a, sep), IgnoreBytes(&b, sep));
+        assert_ne!(IgnoreBytes(&a, b"-"), IgnoreBytes(&b, b"-"));
+        assert!(IgnoreBytes(&a, sep) < IgnoreBytes(&c, sep));
+        assert_eq!(
+            IgnoreBytes(&c, sep).cmp(&IgnoreBytes(&b, sep)),
+            cmp::Ordering::Greater
+        );
+        // Each side uses its own set.
+        assert_eq!(IgnoreBytes(&a, sep), IgnoreBytes(&b, b""));
+        // A missing character sorts first, like a shorter `str`.
+        let abc: StringWrapper<[u8; 16]> = StringWrapper::from_str("a_b_c").unwrap();
+        assert!(IgnoreBytes(&abc, sep) < IgnoreBytes(&b, sep));
+    }
 
-        let s2 = stack_format!(4, "{}", 12345);
-        s2.expect_err("expected error");
+    #[test]
+    fn char_boundaries() {
+        let s: StringWrapper<[u8; 16]> = StringWrapper::from_str("aé☃🌠b").unwrap();
+        assert_eq!(
+            s.char_boundaries().collect::<Vec<_>>(),
+            [0, 1, 3, 6, 10, 11]
+        );
+        assert!(s.char_boundaries().all(|i| s.is_char_boundary(i)));
+        let empty = StringWrapper::new([0_u8; 4]);
+        assert_eq!(empty.char_boundaries().collect::<Vec<_>>(), [0]);
+    }
+
+    #[test]
+    fn extend_wrappers() {
+        let parts: Vec<StringWrapper<[u8; 4]>> = ["ab", "cé", "dé", "e"]
+            .iter()
+            .map(|s| StringWrapper::from_str(s).unwrap())
+            .collect();
+        let mut s = StringWrapper::new([0_u8; 16]);
+        s.extend(&parts);
+        assert_eq!(&*s, "abcédée");
+
+        // "dé" doesn't fit in the 2 bytes left: only its "d" is appended, and "e" isn't.
+        let mut small = StringWrapper::new([0_u8; 7]);
+        small.extend(&parts);
+        assert_eq!(&*small, "abcéd");
+        let mut iter = parts.iter();
+        small.truncate(0);
+        small.extend(&mut iter);
+        assert_eq!(iter.next().map(|s| &**s), Some("e"));
+    }
+
+    #[test]
+    fn byte_at() {
+        let mut s = StringWrapper::new([b'x'; 8]);
+        s.push_str("aé").unwrap();
+        assert_eq!(s.byte_at(0), Some(b'a'));
+        assert_eq!(s.byte_at(1), Some(0xc3));
+        assert_eq!(s.byte_at(2), Some(0xa9));
+        // The buffer has more bytes, but they're not part of the string.
+        assert_eq!(s.buffer()[3], b'x');
+        assert_eq!(s.byte_at(3), None);
+        assert_eq!(s.byte_at(8), None);
+    }
+
+    #[test]
+    fn translate_ascii() {
+        let mut rot13 = [0_u8; 128];
+        for (b, t) in rot13.iter_mut().enumerate() {
+            let b = b as u8;
+            *t = match b {
+                b'a'..=b'z' => (b - b'a' + 13) % 26 + b'a',
+                b'A'..=b'Z' => (b - b'A' + 13) % 26 + b'A',
+                _ => b,
+            };
+        }
+        let mut s: StringWrapper<[u8; 32]> = StringWrapper::from_str("Héllo, Wörld! 42").unwrap();
+        s.translate_ascii(&rot13);
+        assert_eq!(&*s, "Uéyyb, Jöeyq! 42");
+        s.translate_ascii(&rot13);
+        assert_eq!(&*s, "Héllo, Wörld! 42");
+    }
+
+    #[test]
+    #[should_panic(expected = "translation table must only map to ASCII")]
+    fn translate_ascii_non_ascii_table() {
+        let mut s = StringWrapper::new([0_u8; 4]);
+        s.translate_ascii(&[0x80; 128]);
+    }
+
+    #[test]
+    fn into_raw_parts() {
+        let s: StringWrapper<[u8; 8]> = StringWrapper::from_str("héllo").unwrap();
+        let (mut buffer, len) = s.into_raw_parts();
+        assert_eq!(len, 6);
+        assert_eq!(&buffer[..len], "héllo".as_bytes());
+        buffer[0] = b'j';
+        let s = unsafe { StringWrapper::from_raw_parts(buffer, len) };
+        assert_eq!(&*s, "jéllo");
+
+        let mut v = StringWrapper::with_vec_capacity(8);
+        v.push_str("abc").unwrap();
+        let (buffer, len) = v.into_raw_parts();
+        assert_eq!((buffer.len(), len), (8, 3));
+    }
+
+    #[test]
+    fn test_display_joined() {
+        let items: Vec<StringWrapper<[u8; 8]>> = ["foo", "bär", "☃"]
+            .iter()
+            .map(|s| StringWrapper::from_str(s).unwrap())
+            .collect();
+        assert_eq!(
+            format!("[{}]", display_joined(&items, ", ")),
+            "[foo, bär, ☃]"
+        );
+        assert_eq!(display_joined(&items[..1], ", ").to_string(), "foo");
+        assert_eq!(display_joined(&items[..0], ", ").to_string(), "");
+    }
+
+    #[test]
+    fn to_fixed_bytes() {
+        let s: StringWrapper<[u8; 8]> = StringWrapper::from_str("aé").unwrap();
+        assert_eq!(s.to_fixed_bytes::<5>(b' '), *b"a\xc3\xa9  ");
+        assert_eq!(s.to_fixed_bytes::<3>(0), *b"a\xc3\xa9");
+        // Cutting at 2 would split the "é".
+        assert_eq!(s.to_fixed_bytes::<2>(0), *b"a\0");
+        assert_eq!(s.to_fixed_bytes::<0>(0), [0_u8; 0]);
+    }
+
+    #[test]
+    fn char_indices_rev() {
+        let s: StringWrapper<[u8; 16]> = StringWrapper::from_str("aé☃🌠").unwrap();
+        assert_eq!(
+            s.char_indices_rev().collect::<Vec<_>>(),
+            [(6, '🌠'), (3, '☃'), (1, 'é'), (0, 'a')]
+        );
+        assert!(s.char_indices_rev().all(|(i, c)| s.char_at(i) == Some(c)));
     }
 }